@@ -57,7 +57,7 @@ fn gen_solutions_mod<P: AsRef<Path>>(p: P, days: &Vec<u32>) -> io::Result<()> {
     writeln!(f, "use crate::solver::Solver;")?;
     writeln!(f)?;
     for day in days {
-        writeln!(f, "mod day{0:02};", day)?;
+        writeln!(f, "pub mod day{0:02};", day)?;
     }
     writeln!(f)?;
     writeln!(
@@ -0,0 +1,62 @@
+use crate::grid::Grid;
+use std::mem;
+
+// cells of an automaton come in a few kinds, numbered from 0,
+// the neighbours of a cell are counted by kind
+pub trait Cell: Copy {
+    const KINDS: usize;
+
+    fn kind(self) -> usize;
+}
+
+impl Cell for bool {
+    const KINDS: usize = 2;
+
+    fn kind(self) -> usize {
+        self as usize
+    }
+}
+
+// a grid of cells that all change at once, depending on their 8 neighbours
+// anything outside the grid doesn't count as a neighbour
+pub struct Automaton<T> {
+    cells: Grid<T>,
+    // the next generation is written here, then both grids are swapped
+    next: Grid<T>,
+    counts: Vec<usize>,
+}
+
+impl<T: Cell> Automaton<T> {
+    pub fn new(cells: Grid<T>) -> Self {
+        Self {
+            next: cells.clone(),
+            counts: vec![0; T::KINDS],
+            cells,
+        }
+    }
+
+    pub fn cells(&self) -> &Grid<T> {
+        &self.cells
+    }
+
+    // the rule gets a cell and how many of its neighbours there are of each kind,
+    // and returns what the cell becomes
+    pub fn step(&mut self, rule: impl Fn(T, &[usize]) -> T) {
+        for p in self.cells.points() {
+            for c in self.counts.iter_mut() {
+                *c = 0;
+            }
+            for n in self.cells.neighbours8(p) {
+                if let Some(&c) = self.cells.get(n) {
+                    self.counts[c.kind()] += 1;
+                }
+            }
+
+            if let Some(&c) = self.cells.get(p) {
+                self.next.set(p, rule(c, &self.counts));
+            }
+        }
+
+        mem::swap(&mut self.cells, &mut self.next);
+    }
+}
@@ -0,0 +1,819 @@
+use crate::{grid::Grid, point::Point, search::distance_field};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter, Write},
+    io, mem, thread,
+};
+
+// the elves won without losing anyone
+#[derive(Clone, Copy, Debug)]
+pub struct FlawlessVictory {
+    pub elf_atk: usize,
+    pub rounds: usize,
+    pub hp: usize,
+}
+
+impl FlawlessVictory {
+    pub fn outcome(&self) -> usize {
+        self.rounds * self.hp
+    }
+}
+
+// the lowest elf attack power for which no elf dies, if there is one up to max_elf_atk
+// powers are tried in batches, one per available thread, the lowest that works in the first
+// batch with one wins
+pub fn flawless_victory(map: &Map) -> Option<FlawlessVictory> {
+    let candidates = (4..=map.max_elf_atk()).collect::<Vec<_>>();
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    for batch in candidates.chunks(threads) {
+        let victories = thread::scope(|s| {
+            let battles = batch
+                .iter()
+                .map(|&atk| s.spawn(move || map.flawless_battle(atk)))
+                .collect::<Vec<_>>();
+            battles
+                .into_iter()
+                .map(|b| b.join().expect("battle thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        if let Some(v) = victories.into_iter().flatten().next() {
+            return Some(v);
+        }
+    }
+
+    None
+}
+
+#[derive(Clone)]
+pub struct Map {
+    cells: Grid<Elem>,
+    rounds: usize,
+    config: Config,
+    // the battle is lost for the elves as soon as one of them dies
+    flawless: bool,
+    elves_lost: usize,
+    // only recorded when asked for
+    events: Option<Vec<Event>>,
+}
+
+// what happens during a battle, in order, positions are the ones before the event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Moved {
+        unit: Faction,
+        from: Point,
+        to: Point,
+    },
+    Attacked {
+        unit: Faction,
+        pos: Point,
+        target: Point,
+        damage: usize,
+        hp: usize,
+    },
+    Died {
+        unit: Faction,
+        pos: Point,
+    },
+    RoundCompleted {
+        round: usize,
+    },
+    BattleEnded {
+        winner: Faction,
+        rounds: usize,
+        hp: usize,
+    },
+}
+
+impl Map {
+    pub fn from_reader<R: io::Read>(r: R) -> Self {
+        let cells = Grid::from_reader(r, Elem::from_char).expect("unable to parse map");
+
+        Map {
+            cells,
+            rounds: 0,
+            config: Config::default(),
+            flawless: false,
+            elves_lost: 0,
+            events: None,
+        }
+    }
+
+    // units get the hit points of their faction, so this is to be done before fighting
+    pub fn set_config(&mut self, config: Config) {
+        for p in self.cells.points() {
+            if let Some(u) = self.elem(p).unit() {
+                let hp = config.stats(u.faction).hp;
+                self.replace_elem(Elem::Unit(Unit { hp, ..u }), p);
+            }
+        }
+        self.config = config;
+    }
+
+    // with this much attack power, elves kill any enemy in one hit,
+    // so the battle goes the same way with any higher power
+    pub fn max_elf_atk(&self) -> usize {
+        self.find_units(|f| self.config.hostile(ELVES, f))
+            .iter()
+            .map(|u| u.unit.hp)
+            .max()
+            .unwrap_or(0)
+            .max(4)
+    }
+
+    pub fn set_elf_atk(&mut self, elf_atk: usize) {
+        self.config.factions.entry(ELVES).or_default().atk = elf_atk;
+    }
+
+    pub fn record_events(&mut self) {
+        self.events = Some(vec![]);
+    }
+
+    pub fn take_events(&mut self) -> Vec<Event> {
+        self.events.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    fn emit(&mut self, event: Event) {
+        if let Some(events) = &mut self.events {
+            events.push(event);
+        }
+    }
+
+    // rounds are fought until one side has no enemies left
+    pub fn battle(&mut self) -> Faction {
+        loop {
+            if let Status::Win(t) = self.round() {
+                return t;
+            }
+        }
+    }
+
+    // a battle with the given elf attack power that stops as soon as an elf dies
+    fn flawless_battle(&self, elf_atk: usize) -> Option<FlawlessVictory> {
+        let mut map = self.clone();
+        map.set_elf_atk(elf_atk);
+        map.flawless = true;
+
+        loop {
+            match map.round() {
+                Status::Continue => {}
+                Status::Win(ELVES) => {
+                    return Some(FlawlessVictory {
+                        elf_atk,
+                        rounds: map.rounds,
+                        hp: map.remaining_hp(),
+                    });
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    // replays an event from a recorded battle, the units are not checked against the map
+    pub fn apply(&mut self, event: &Event) -> Result<(), String> {
+        let unit_at = |pos: Point| {
+            self.elem(pos)
+                .unit()
+                .ok_or_else(|| format!("no unit at {}", pos))
+        };
+
+        match *event {
+            Event::Moved { from, to, .. } => {
+                unit_at(from)?;
+                self.move_unit(from, to);
+            }
+            Event::Attacked { target, hp, .. } => {
+                let unit = unit_at(target)?;
+                self.replace_elem(Elem::Unit(Unit { hp, ..unit }), target);
+            }
+            Event::Died { pos, .. } => {
+                unit_at(pos)?;
+                self.delete_unit(pos);
+            }
+            Event::RoundCompleted { round } => self.rounds = round,
+            Event::BattleEnded { rounds, .. } => self.rounds = rounds,
+        }
+
+        Ok(())
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    // the whole battle laid out as in the puzzle, the board after each full round,
+    // the last one showing how the battle ended, then the outcome
+    pub fn render_battle(&mut self) -> String {
+        let mut out = format!("Initially:\n{}", self);
+
+        let mut boards = vec![];
+        let winner = loop {
+            match self.round() {
+                Status::Win(t) => break t,
+                _ => boards.push(self.to_string()),
+            }
+        };
+        boards.pop();
+        boards.push(self.to_string());
+
+        for (i, b) in boards.iter().enumerate() {
+            let s = if i == 0 { "" } else { "s" };
+            out += &format!("\nAfter {} round{}:\n{}", i + 1, s, b);
+        }
+
+        let hp = self.remaining_hp();
+        let winners = winner.name();
+        out += &format!(
+            "\nCombat ends after {} full rounds\n\
+             {} win with {} total hit points left\n\
+             Outcome: {} * {} = {}\n",
+            self.rounds,
+            winners,
+            hp,
+            self.rounds,
+            hp,
+            self.rounds * hp
+        );
+
+        out
+    }
+
+    fn round(&mut self) -> Status {
+        for pos in self.cells.points() {
+            let elem = self.elem(pos);
+
+            if let Some(unit) = elem.unit() {
+                let unit_pos = UnitPos { unit, pos };
+
+                let status = self.turn(unit_pos);
+                if let Status::Win(t) = status {
+                    self.emit(Event::BattleEnded {
+                        winner: t,
+                        rounds: self.rounds,
+                        hp: self.remaining_hp(),
+                    });
+                    return status;
+                }
+                if self.flawless && self.elves_lost > 0 {
+                    return Status::ElfDied;
+                }
+            }
+        }
+
+        self.rounds += 1;
+        self.emit(Event::RoundCompleted { round: self.rounds });
+        Status::Continue
+    }
+
+    fn turn(&mut self, unit_pos: UnitPos) -> Status {
+        // don't handle this unit's turn if it has done it already
+        if unit_pos.unit.rounds > self.rounds {
+            return Status::Continue;
+        }
+
+        // identify all enemy units
+        let faction = unit_pos.unit.faction;
+        let enemies = self.find_units(|f| self.config.hostile(faction, f));
+
+        if enemies.is_empty() {
+            return Status::Win(faction);
+        }
+
+        // current position
+        let mut new_pos = unit_pos.pos;
+
+        // are we in range of an enemy? (eg. find enemies up/left/right/down)
+        // if not move
+        let enemies_in_range = self.enemies_in_range(&unit_pos);
+        if enemies_in_range.is_empty() {
+            let pos = self.mv(&unit_pos, &enemies);
+
+            if unit_pos.pos != pos {
+                self.move_unit(unit_pos.pos, pos);
+                self.emit(Event::Moved {
+                    unit: unit_pos.unit.faction,
+                    from: unit_pos.pos,
+                    to: pos,
+                });
+                new_pos = pos;
+            } else {
+                // can't move, end turn
+                // commit unit change
+                let e = Elem::Unit(Unit {
+                    faction: unit_pos.unit.faction,
+                    hp: unit_pos.unit.hp,
+                    rounds: unit_pos.unit.rounds + 1,
+                });
+                self.replace_elem(e, unit_pos.pos);
+
+                return Status::Continue;
+            }
+        }
+
+        // get new element
+        let unit_pos_new = UnitPos {
+            unit: self.elem(new_pos).unit().unwrap(),
+            pos: new_pos,
+        };
+
+        // now, again, are we in range of an enemy?
+        // if so, attack !!!
+        let enemies_in_range = self.enemies_in_range(&unit_pos_new);
+        if !enemies_in_range.is_empty() {
+            self.attack(&unit_pos_new, &enemies_in_range);
+        }
+
+        // commit unit change
+        let e = Elem::Unit(Unit {
+            faction: unit_pos_new.unit.faction,
+            hp: unit_pos_new.unit.hp,
+            rounds: unit_pos_new.unit.rounds + 1,
+        });
+        self.replace_elem(e, unit_pos_new.pos);
+
+        Status::Continue
+    }
+
+    fn attack(&mut self, attacker: &UnitPos, enemies: &[UnitPos]) {
+        let min_hp = enemies.iter().map(|e| e.unit.hp).min().unwrap();
+
+        for enemy in enemies.iter() {
+            // prioritize enemies with low hp
+            if enemy.unit.hp != min_hp {
+                continue;
+            }
+
+            let atk_power = self.config.stats(attacker.unit.faction).atk as isize;
+
+            let hp_after_atk = enemy.unit.hp as isize - atk_power;
+            self.emit(Event::Attacked {
+                unit: attacker.unit.faction,
+                pos: attacker.pos,
+                target: enemy.pos,
+                damage: atk_power as usize,
+                hp: hp_after_atk.max(0) as usize,
+            });
+            if hp_after_atk <= 0 {
+                self.delete_unit(enemy.pos);
+                if enemy.unit.faction == ELVES {
+                    self.elves_lost += 1;
+                }
+                self.emit(Event::Died {
+                    unit: enemy.unit.faction,
+                    pos: enemy.pos,
+                });
+            } else {
+                let e = Elem::Unit(Unit {
+                    faction: enemy.unit.faction,
+                    hp: hp_after_atk as usize,
+                    rounds: enemy.unit.rounds,
+                });
+                self.replace_elem(e, enemy.pos);
+            }
+
+            break;
+        }
+    }
+
+    fn mv(&mut self, unit: &UnitPos, enemies: &[UnitPos]) -> Point {
+        // find all open squares in range
+        let open_squares = self.find_open_squares(enemies);
+
+        // if none available, just stop here
+        if open_squares.is_empty() {
+            return unit.pos;
+        }
+
+        // the closest reachable square, in reading order for the same distance
+        let field = distance_field(&self.cells, unit.pos, |e| matches!(e, Elem::Open));
+        let best = open_squares
+            .iter()
+            .filter_map(|&sq| {
+                field
+                    .get(sq)
+                    .cloned()
+                    .flatten()
+                    .map(|(d, first)| (d, sq, first))
+            })
+            .min();
+
+        best.map_or(unit.pos, |(_, _, first)| first)
+    }
+
+    fn find_open_squares(&self, enemies: &[UnitPos]) -> Vec<Point> {
+        let mut squares = vec![];
+
+        for enemy in enemies.iter() {
+            for pos in self.neighbours(enemy.pos) {
+                if let Elem::Open = self.elem(pos) {
+                    squares.push(pos);
+                }
+            }
+        }
+
+        squares
+    }
+
+    // units of the factions for which the predicate is true, in reading order
+    fn find_units(&self, faction: impl Fn(Faction) -> bool) -> Vec<UnitPos> {
+        let mut units = vec![];
+
+        for pos in self.cells.points() {
+            let elem = self.elem(pos);
+            if let Some(unit) = elem.unit() {
+                if faction(unit.faction) {
+                    units.push(UnitPos { unit, pos });
+                }
+            }
+        }
+
+        units
+    }
+
+    // the hit points of all the units still standing
+    pub fn remaining_hp(&self) -> usize {
+        self.find_units(|_| true).iter().map(|u| u.unit.hp).sum()
+    }
+
+    fn enemies_in_range(&self, unit_pos: &UnitPos) -> Vec<UnitPos> {
+        let mut units = vec![];
+
+        for pos in self.neighbours(unit_pos.pos) {
+            let e = self.elem(pos).unit();
+            if let Some(u) = e {
+                if self.config.hostile(unit_pos.unit.faction, u.faction) {
+                    units.push(UnitPos { unit: u, pos });
+                }
+            }
+        }
+
+        units
+    }
+
+    // neighbours by reading order
+    fn neighbours(&self, pos: Point) -> impl Iterator<Item = Point> + '_ {
+        self.cells.neighbours4(pos)
+    }
+
+    fn elem(&self, pos: Point) -> Elem {
+        // outside of the map is as good as a wall
+        self.cells.get(pos).cloned().unwrap_or(Elem::Wall)
+    }
+
+    fn replace_elem(&mut self, elem: Elem, pos: Point) {
+        self.cells.set(pos, elem);
+    }
+
+    fn delete_unit(&mut self, pos: Point) {
+        self.replace_elem(Elem::Open, pos);
+    }
+
+    fn move_unit(&mut self, from: Point, to: Point) {
+        let elem = self.elem(from);
+        self.delete_unit(from);
+        self.replace_elem(elem, to);
+    }
+}
+
+// each row of the map followed by the hit points of its units, as in the puzzle
+impl Display for Map {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for row in self.cells.rows() {
+            for &e in row {
+                f.write_char(e.into())?;
+            }
+
+            let units = row
+                .iter()
+                .filter_map(|&e| e.unit().map(|u| format!("{}({})", char::from(e), u.hp)))
+                .collect::<Vec<_>>();
+            if !units.is_empty() {
+                write!(f, "   {}", units.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Eq, PartialEq)]
+enum Status {
+    Continue,
+    Win(Faction),
+    ElfDied,
+}
+
+#[derive(Copy, Clone)]
+pub enum Elem {
+    Unit(Unit),
+    Open,
+    Wall,
+}
+
+impl From<Elem> for char {
+    fn from(elem: Elem) -> char {
+        match elem {
+            Elem::Unit(u) => u.faction.0,
+            Elem::Open => '.',
+            Elem::Wall => '#',
+        }
+    }
+}
+
+impl Elem {
+    fn from_char(c: char) -> Option<Elem> {
+        match c {
+            '.' => Some(Elem::Open),
+            'A'..='Z' => Some(Elem::Unit(Unit::new(Faction(c)))),
+            '#' => Some(Elem::Wall),
+            _ => None,
+        }
+    }
+
+    fn unit(&self) -> Option<Unit> {
+        match self {
+            Elem::Unit(u) => Some(*u),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Unit {
+    faction: Faction,
+    hp: usize,
+    rounds: usize,
+}
+
+impl Unit {
+    fn new(faction: Faction) -> Self {
+        Self {
+            faction,
+            hp: Stats::default().hp,
+            rounds: 0,
+        }
+    }
+}
+
+// units belong to the faction of the letter that shows them on the map
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Faction(char);
+
+pub const ELVES: Faction = Faction('E');
+pub const GOBLINS: Faction = Faction('G');
+
+impl Faction {
+    fn name(self) -> String {
+        match self {
+            ELVES => "Elves".to_string(),
+            GOBLINS => "Goblins".to_string(),
+            Faction(c) => format!("{} units", c),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub hp: usize,
+    pub atk: usize,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self { hp: 200, atk: 3 }
+    }
+}
+
+// unit stats by faction, the factions that are not listed get the default ones
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub factions: HashMap<Faction, Stats>,
+    // every faction fights all the others, otherwise it's the elves against everyone else
+    pub free_for_all: bool,
+}
+
+impl Config {
+    fn stats(&self, f: Faction) -> Stats {
+        self.factions.get(&f).cloned().unwrap_or_default()
+    }
+
+    fn hostile(&self, a: Faction, b: Faction) -> bool {
+        a != b && (self.free_for_all || a == ELVES || b == ELVES)
+    }
+}
+
+struct UnitPos {
+    unit: Unit,
+    pos: Point,
+}
+
+// compares a rendered battle with a golden one, which may only have some of its blocks
+// blocks are separated by empty lines and start with a header, like "After 2 rounds:",
+// each golden block is checked against the rendered block with the same header
+// returns the first golden block that differs, and the rendered one
+pub fn golden_mismatch(golden: &str, rendered: &str) -> Option<(String, String)> {
+    let blocks = |s: &str| {
+        s.split("\n\n")
+            .map(|b| b.trim_end_matches('\n').to_string())
+            .filter(|b| !b.is_empty())
+            .collect::<Vec<_>>()
+    };
+    let header = |b: &str| b.lines().next().unwrap_or_default().to_string();
+
+    let rendered = blocks(rendered);
+    blocks(golden).into_iter().find_map(|g| {
+        match rendered.iter().find(|r| header(r) == header(&g)) {
+            Some(r) if *r == g => None,
+            r => Some((
+                g,
+                r.cloned().unwrap_or_else(|| String::from("no such block")),
+            )),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the map without the hit points
+    fn cells(map: &Map) -> String {
+        map.to_string()
+            .lines()
+            .map(|l| format!("{}\n", l.split(' ').next().unwrap_or_default()))
+            .collect()
+    }
+
+    // where the first unit in reading order moves on its turn
+    fn first_move(map: &str) -> Point {
+        let mut map = Map::from_reader(map.as_bytes());
+        let unit = map.find_units(|_| true).remove(0);
+        let enemies = map.find_units(|f| f != unit.unit.faction);
+
+        map.mv(&unit, &enemies)
+    }
+
+    #[test]
+    fn chosen_square() {
+        let map = "#######
+#E..G.#
+#...#.#
+#.G.#G#
+#######
+";
+        assert_eq!(first_move(map), Point::new(2, 1));
+
+        let map = "#######
+#.E...#
+#.....#
+#...G.#
+#######
+";
+        assert_eq!(first_move(map), Point::new(3, 1));
+    }
+
+    #[test]
+    fn movement() {
+        let mut map = Map::from_reader(
+            "#########
+#G..G..G#
+#.......#
+#.......#
+#G..E..G#
+#.......#
+#.......#
+#G..G..G#
+#########
+"
+            .as_bytes(),
+        );
+        let rounds = [
+            "#########
+#.G...G.#
+#...G...#
+#...E..G#
+#.G.....#
+#.......#
+#G..G..G#
+#.......#
+#########
+",
+            "#########
+#..G.G..#
+#...G...#
+#.G.E.G.#
+#.......#
+#G..G..G#
+#.......#
+#.......#
+#########
+",
+            "#########
+#.......#
+#..GGG..#
+#..GEG..#
+#G..G...#
+#......G#
+#.......#
+#.......#
+#########
+",
+        ];
+
+        for expected in rounds.iter() {
+            assert!(map.round() == Status::Continue);
+            assert_eq!(cells(&map), *expected);
+        }
+    }
+
+    #[test]
+    fn outcomes() {
+        let examples = [
+            (include_str!("../golden/day15/ex1.map"), 47, 27730),
+            (include_str!("../golden/day15/ex2.map"), 37, 36334),
+            (include_str!("../golden/day15/ex3.map"), 46, 39514),
+            (include_str!("../golden/day15/ex4.map"), 35, 27755),
+            (include_str!("../golden/day15/ex5.map"), 54, 28944),
+            (include_str!("../golden/day15/ex6.map"), 20, 18740),
+        ];
+
+        for &(map, rounds, outcome) in examples.iter() {
+            let mut map = Map::from_reader(map.as_bytes());
+            map.battle();
+            assert_eq!(map.rounds(), rounds);
+            assert_eq!(map.rounds() * map.remaining_hp(), outcome);
+        }
+    }
+
+    #[test]
+    fn flawless_victories() {
+        let examples = [
+            (include_str!("../golden/day15/ex1.map"), 15, 4988),
+            (include_str!("../golden/day15/ex3.map"), 4, 31284),
+            (include_str!("../golden/day15/ex4.map"), 15, 3478),
+            (include_str!("../golden/day15/ex5.map"), 12, 6474),
+            (include_str!("../golden/day15/ex6.map"), 34, 1140),
+        ];
+
+        for &(map, elf_atk, outcome) in examples.iter() {
+            let v = flawless_victory(&Map::from_reader(map.as_bytes())).unwrap();
+            assert_eq!((v.elf_atk, v.outcome()), (elf_atk, outcome));
+        }
+    }
+
+    #[test]
+    fn no_flawless_victory() {
+        // the first goblin kills the elf before it can do anything
+        let mut map = Map::from_reader("#####\n#GEG#\n#####\n".as_bytes());
+        let mut config = Config::default();
+        config.factions.insert(GOBLINS, Stats { hp: 50, atk: 200 });
+        map.set_config(config);
+
+        assert_eq!(map.max_elf_atk(), 50);
+        assert!(flawless_victory(&map).is_none());
+    }
+
+    #[test]
+    fn goldens() {
+        let examples = [
+            (
+                include_str!("../golden/day15/ex1.map"),
+                include_str!("../golden/day15/ex1.txt"),
+            ),
+            (
+                include_str!("../golden/day15/ex2.map"),
+                include_str!("../golden/day15/ex2.txt"),
+            ),
+            (
+                include_str!("../golden/day15/ex3.map"),
+                include_str!("../golden/day15/ex3.txt"),
+            ),
+            (
+                include_str!("../golden/day15/ex4.map"),
+                include_str!("../golden/day15/ex4.txt"),
+            ),
+            (
+                include_str!("../golden/day15/ex5.map"),
+                include_str!("../golden/day15/ex5.txt"),
+            ),
+            (
+                include_str!("../golden/day15/ex6.map"),
+                include_str!("../golden/day15/ex6.txt"),
+            ),
+        ];
+
+        for &(map, golden) in examples.iter() {
+            let rendered = Map::from_reader(map.as_bytes()).render_battle();
+            if let Some((expected, actual)) = golden_mismatch(golden, &rendered) {
+                panic!("expected:\n{}\nactual:\n{}", expected, actual);
+            }
+        }
+    }
+}
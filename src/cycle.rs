@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    ops::{Mul, Sub},
+};
+
+// the state after the given number of steps, skipping ahead as soon as a state repeats
+// step updates the state in place, key gives the part of a state that is kept to find
+// repeats, which are compared for equality, not only by their hash
+pub fn simulate<S, K>(
+    initial: S,
+    steps: usize,
+    step: impl FnMut(&mut S),
+    key: impl Fn(&S) -> K,
+) -> S
+where
+    K: Eq + Hash,
+{
+    simulate_drifting(initial, steps, step, |s| (key(s), 0), |s, _| s)
+}
+
+// same as simulate, for states that can come back moved by some offset, like a glider
+// split gives the shape of a state, which is what gets compared, and its offset,
+// shift moves a state by the given offset
+pub fn simulate_drifting<S, K, D>(
+    initial: S,
+    steps: usize,
+    mut step: impl FnMut(&mut S),
+    split: impl Fn(&S) -> (K, D),
+    shift: impl Fn(S, D) -> S,
+) -> S
+where
+    K: Eq + Hash,
+    D: Copy + Sub<Output = D> + Mul<i64, Output = D>,
+{
+    let mut seen = HashMap::new();
+    let mut state = initial;
+
+    for n in 0..steps {
+        let (shape, offset) = split(&state);
+        if let Some(&(start, start_offset)) = seen.get(&shape) {
+            // from the first repeat on, the shape comes back every period steps,
+            // moved by drift each time
+            let period = n - start;
+            let drift = offset - start_offset;
+
+            // whole cycles are skipped at once, what is left is simulated
+            let remaining = steps - n;
+            state = shift(state, drift * (remaining / period) as i64);
+            for _ in 0..remaining % period {
+                step(&mut state);
+            }
+            return state;
+        }
+
+        seen.insert(shape, (n, offset));
+        step(&mut state);
+    }
+
+    state
+}
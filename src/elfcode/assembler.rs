@@ -0,0 +1,278 @@
+use super::{Inst, Opcode, Program, N_REGISTERS};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter, Write},
+};
+
+// source syntax, one statement per line:
+//   #ip <register>          binds the ip register, which can then be called "ip"
+//   .reg <name> <register>  gives a name to a register
+//   <label>:                marks the address of the next instruction
+//   <opcode> <a> <b> <c>    registers are written r0..r5, by name or as plain numbers,
+//                           values are numbers or labels, optionally +/- an offset
+//   jmp <label>             jumps to a label, eg. seti <label - 1> 0 ip
+//   ; comment
+
+#[derive(Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+struct Statement<'a> {
+    line: usize,
+    words: Vec<&'a str>,
+}
+
+struct Symbols<'a> {
+    ip_register: Option<usize>,
+    registers: HashMap<&'a str, usize>,
+    labels: HashMap<&'a str, u32>,
+}
+
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let mut symbols = Symbols {
+        ip_register: None,
+        registers: HashMap::new(),
+        labels: HashMap::new(),
+    };
+    let mut statements = vec![];
+
+    // first pass, find directives and labels
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let mut code = line.split(';').next().unwrap_or("").trim();
+
+        // labels, possibly followed by an instruction
+        while let Some(pos) = code.find(':') {
+            let label = code[..pos].trim();
+            if !is_identifier(label) {
+                return Err(AsmError::new(line_no, format!("invalid label: {}", label)));
+            }
+            let address = statements.len() as u32;
+            if symbols.labels.insert(label, address).is_some() {
+                return Err(AsmError::new(
+                    line_no,
+                    format!("duplicate label: {}", label),
+                ));
+            }
+            code = code[pos + 1..].trim();
+        }
+
+        let words = code.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] => {}
+            ["#ip", r] => {
+                let r = parse_register(r, &HashMap::new())
+                    .ok_or_else(|| AsmError::new(line_no, format!("invalid register: {}", r)))?;
+                symbols.ip_register = Some(r);
+                symbols.registers.insert("ip", r);
+            }
+            [".reg", name, r] => {
+                let r = parse_register(r, &symbols.registers)
+                    .ok_or_else(|| AsmError::new(line_no, format!("invalid register: {}", r)))?;
+                if !is_identifier(name) {
+                    return Err(AsmError::new(line_no, format!("invalid name: {}", name)));
+                }
+                symbols.registers.insert(name, r);
+            }
+            [directive, ..] if directive.starts_with('#') || directive.starts_with('.') => {
+                return Err(AsmError::new(
+                    line_no,
+                    format!("invalid directive: {}", code),
+                ));
+            }
+            _ => statements.push(Statement {
+                line: line_no,
+                words,
+            }),
+        }
+    }
+
+    // second pass, resolve operands
+    let instructions = statements
+        .iter()
+        .map(|s| assemble_statement(s, &symbols))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Program {
+        ip_register: symbols.ip_register.unwrap_or(0),
+        instructions,
+    })
+}
+
+fn assemble_statement(s: &Statement, symbols: &Symbols) -> Result<Inst, AsmError> {
+    let error = |message: String| AsmError::new(s.line, message);
+
+    if let ["jmp", target] = s.words.as_slice() {
+        let ip = symbols
+            .ip_register
+            .ok_or_else(|| error("jmp needs an #ip directive".to_string()))?;
+        let target = parse_value(target, &symbols.labels)
+            .ok_or_else(|| error(format!("invalid value: {}", target)))?;
+
+        // the ip register is incremented after the jump
+        let target = target
+            .checked_sub(1)
+            .ok_or_else(|| error("cannot jump to address 0".to_string()))?;
+
+        return Ok(Inst {
+            opcode: Opcode::Seti,
+            args: [target, 0, ip as u32],
+        });
+    }
+
+    let (name, operands) = match s.words.split_first() {
+        Some((name, operands)) if operands.len() == 3 => (name, operands),
+        _ => return Err(error(format!("expected 3 operands: {}", s.words.join(" ")))),
+    };
+
+    let opcode = Opcode::parse(name).ok_or_else(|| error(format!("unknown opcode: {}", name)))?;
+    let (a_reg, b_reg) = opcode.operands();
+
+    let mut args = [0; 3];
+    for (arg, (&word, is_reg)) in args
+        .iter_mut()
+        .zip(operands.iter().zip([a_reg, b_reg, true].iter()))
+    {
+        *arg = if *is_reg {
+            parse_register(word, &symbols.registers)
+                .map(|r| r as u32)
+                .ok_or_else(|| error(format!("invalid register: {}", word)))?
+        } else {
+            parse_value(word, &symbols.labels)
+                .ok_or_else(|| error(format!("invalid value: {}", word)))?
+        };
+    }
+
+    Ok(Inst { opcode, args })
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_register(s: &str, names: &HashMap<&str, usize>) -> Option<usize> {
+    let r = match names.get(s) {
+        Some(&r) => r,
+        None => s.strip_prefix('r').unwrap_or(s).parse().ok()?,
+    };
+    Some(r).filter(|&r| r < N_REGISTERS)
+}
+
+fn parse_value(s: &str, labels: &HashMap<&str, u32>) -> Option<u32> {
+    if let Ok(v) = s.parse() {
+        return Some(v);
+    }
+
+    let (label, offset) = match s.find(['+', '-']) {
+        Some(pos) => (&s[..pos], s[pos..].trim_start_matches('+').parse().ok()?),
+        None => (s, 0i64),
+    };
+
+    let address = i64::from(*labels.get(label)?) + offset;
+    u32::try_from(address).ok()
+}
+
+// reads a list of opcode names, the position of each name being its number
+pub fn parse_mapping(s: &str) -> Result<Vec<Opcode>, AsmError> {
+    let mut mapping = vec![];
+
+    for (i, line) in s.lines().enumerate() {
+        for name in line.split_whitespace() {
+            let opcode = Opcode::parse(name)
+                .ok_or_else(|| AsmError::new(i + 1, format!("unknown opcode: {}", name)))?;
+            if mapping.contains(&opcode) {
+                return Err(AsmError::new(i + 1, format!("duplicate opcode: {}", name)));
+            }
+            mapping.push(opcode);
+        }
+    }
+
+    Ok(mapping)
+}
+
+// writes the program in the numeric "opcode a b c" form of day 16, the
+// ip register is kept in a #ip header as this form has no way to express it
+pub fn encode(program: &Program, mapping: &[Opcode]) -> Result<String, AsmError> {
+    let mut s = format!("#ip {}\n", program.ip_register);
+
+    for (i, inst) in program.instructions.iter().enumerate() {
+        let code = mapping
+            .iter()
+            .position(|&o| o == inst.opcode)
+            .ok_or_else(|| AsmError::new(i + 2, format!("unmapped opcode: {}", inst.opcode)))?;
+        writeln!(
+            s,
+            "{} {} {} {}",
+            code, inst.args[0], inst.args[1], inst.args[2]
+        )
+        .expect("unable to write to string");
+    }
+
+    Ok(s)
+}
+
+// reads the numeric form written by encode, the #ip header is optional
+pub fn decode(s: &str, mapping: &[Opcode]) -> Result<Program, AsmError> {
+    let mut ip_register = 0;
+    let mut instructions = vec![];
+
+    for (i, line) in s.lines().enumerate() {
+        let error = |message: String| AsmError::new(i + 1, message);
+
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] => {}
+            ["#ip", r] => {
+                ip_register = r
+                    .parse()
+                    .map_err(|_| error(format!("invalid register: {}", r)))?;
+            }
+            [code, a, b, c] => {
+                let numbers = [code, a, b, c]
+                    .iter()
+                    .map(|n| n.parse::<u32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| error(format!("invalid instruction: {}", line)))?;
+                let opcode = mapping
+                    .get(numbers[0] as usize)
+                    .ok_or_else(|| error(format!("unmapped opcode: {}", code)))?;
+
+                instructions.push(Inst {
+                    opcode: *opcode,
+                    args: [numbers[1], numbers[2], numbers[3]],
+                });
+            }
+            _ => return Err(error(format!("invalid instruction: {}", line))),
+        }
+    }
+
+    Ok(Program {
+        ip_register,
+        instructions,
+    })
+}
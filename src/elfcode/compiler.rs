@@ -0,0 +1,308 @@
+use super::{
+    next_ip_from, Budget, Execution, Fault, Inst, Machine, Mode, Opcode, Outcome, Program,
+    N_REGISTERS,
+};
+use std::time::Instant;
+
+type Registers = [u64; N_REGISTERS];
+
+// a compiled instruction returns the next value of ip
+type Op = Box<dyn Fn(&mut Registers) -> Result<usize, Fault>>;
+
+// a program where every instruction has been turned into a closure ahead of time, with
+// its registers and immediate values already decoded
+//
+// reads of the ip register are replaced by the position of the instruction, and only jumps
+// (instructions writing to the ip register) actually update it, the others leave it to
+// the caller, which makes sure the registers always end up the same as with the interpreter
+//
+// the arithmetic mode is fixed at compile time, the mode of the machine running it is ignored
+pub struct Compiled {
+    ip_register: usize,
+    ops: Vec<Op>,
+    jumps: Vec<bool>,
+}
+
+impl Compiled {
+    pub fn new(program: &Program, mode: Mode) -> Self {
+        let ip_register = program.ip_register;
+        let ops = program
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(ip, inst)| compile(inst, ip, ip_register, mode))
+            .collect();
+        let jumps = program
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(ip, inst)| program.writes_ip(ip) || !inst.has_valid_registers())
+            .collect();
+
+        Self {
+            ip_register,
+            ops,
+            jumps,
+        }
+    }
+
+    // same as Program::execute
+    pub fn execute(&self, vm: &mut Machine, budget: Budget) -> Execution {
+        let start = Instant::now();
+        let registers = &mut vm.registers;
+        let mut n_exec = 0;
+        let mut ip = 0;
+        let mut last_ip = None;
+
+        let outcome = loop {
+            let exhausted = match budget {
+                Budget::Unlimited => false,
+                Budget::Instructions(max) => n_exec >= max,
+                Budget::Time(max) => n_exec % 1024 == 0 && start.elapsed() >= max,
+            };
+            if exhausted {
+                break Outcome::BudgetExhausted;
+            }
+
+            let op = match self.ops.get(ip) {
+                Some(op) => op,
+                None => break Outcome::Halted,
+            };
+
+            if self.ip_register >= N_REGISTERS {
+                break Fault::InvalidRegister(self.ip_register as u32).at(ip);
+            }
+
+            match op(registers) {
+                Ok(next_ip) => {
+                    last_ip = Some(ip);
+                    ip = next_ip;
+                    n_exec += 1;
+                }
+                Err(f) => {
+                    // the interpreter sets the ip register before failing
+                    last_ip = None;
+                    registers[self.ip_register] = ip as u64;
+                    break f.at(ip);
+                }
+            }
+        };
+
+        // catch up on the last ip register update
+        if let Some(last_ip) = last_ip {
+            if !self.jumps[last_ip] {
+                registers[self.ip_register] = last_ip as u64;
+            }
+        }
+
+        Execution {
+            outcome,
+            n_exec,
+            registers: *registers,
+        }
+    }
+
+    // same as Program::step
+    pub fn step(&self, vm: &mut Machine, ip: usize) -> Result<usize, Outcome> {
+        let op = self.ops.get(ip).ok_or(Outcome::Halted)?;
+
+        if self.ip_register >= N_REGISTERS {
+            return Err(Fault::InvalidRegister(self.ip_register as u32).at(ip));
+        }
+
+        vm.registers[self.ip_register] = ip as u64;
+        op(&mut vm.registers).map_err(|f| f.at(ip))
+    }
+}
+
+// operand of a compiled instruction
+#[derive(Clone, Copy)]
+enum Val {
+    Reg(usize),
+    Imm(u64),
+}
+
+fn compile(inst: &Inst, ip: usize, ip_register: usize, mode: Mode) -> Op {
+    // instructions using invalid registers are rare, they are left to the interpreter
+    // so that faults are reported in the exact same way
+    if !inst.has_valid_registers() || ip_register >= N_REGISTERS {
+        let inst = inst.clone();
+        return Box::new(move |r| {
+            let mut vm = Machine {
+                registers: *r,
+                mode,
+            };
+            vm.registers[ip_register] = ip as u64;
+            vm.exec(&inst)?;
+            *r = vm.registers;
+            Ok(next_ip_from(r[ip_register]))
+        });
+    }
+
+    let (a_reg, b_reg) = inst.opcode.operands();
+    let val = |arg: u32, is_reg: bool| match arg as usize {
+        r if is_reg && r == ip_register => Val::Imm(ip as u64),
+        r if is_reg => Val::Reg(r),
+        _ => Val::Imm(arg as u64),
+    };
+    let a = val(inst.args[0], a_reg);
+    let b = val(inst.args[1], b_reg);
+    let c = inst.args[2] as usize;
+
+    // writing to the ip register makes the instruction a jump
+    let next_ip = if c == ip_register { None } else { Some(ip + 1) };
+
+    // mode operations can only fail with an overflow
+    match inst.opcode {
+        Opcode::Addr | Opcode::Addi => {
+            specialise(move |a, b| mode.add(a, b).ok(), a, b, c, next_ip)
+        }
+        Opcode::Mulr | Opcode::Muli => {
+            specialise(move |a, b| mode.mul(a, b).ok(), a, b, c, next_ip)
+        }
+        Opcode::Banr | Opcode::Bani => specialise(|a, b| Some(a & b), a, b, c, next_ip),
+        Opcode::Borr | Opcode::Bori => specialise(
+            move |a, b| mode.fit(u128::from(a | b)).ok(),
+            a,
+            b,
+            c,
+            next_ip,
+        ),
+        Opcode::Setr => specialise(|a, _| Some(a), a, b, c, next_ip),
+        Opcode::Seti => specialise(move |a, _| mode.fit(u128::from(a)).ok(), a, b, c, next_ip),
+        Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr => {
+            specialise(|a, b| Some(u64::from(a > b)), a, b, c, next_ip)
+        }
+        Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr => {
+            specialise(|a, b| Some(u64::from(a == b)), a, b, c, next_ip)
+        }
+    }
+}
+
+// builds a closure for each combination of operand kinds, so that the
+// operation itself gets inlined, None is used on overflow
+fn specialise<F>(f: F, a: Val, b: Val, c: usize, next_ip: Option<usize>) -> Op
+where
+    F: Fn(u64, u64) -> Option<u64> + Copy + 'static,
+{
+    // jumps go wherever their result points to
+    let next = move |val: u64| next_ip.unwrap_or_else(|| next_ip_from(val));
+
+    match (a, b) {
+        (Val::Reg(a), Val::Reg(b)) => Box::new(move |r| {
+            r[c] = f(r[a], r[b]).ok_or(Fault::Overflow)?;
+            Ok(next(r[c]))
+        }),
+        (Val::Reg(a), Val::Imm(b)) => Box::new(move |r| {
+            r[c] = f(r[a], b).ok_or(Fault::Overflow)?;
+            Ok(next(r[c]))
+        }),
+        (Val::Imm(a), Val::Reg(b)) => Box::new(move |r| {
+            r[c] = f(a, r[b]).ok_or(Fault::Overflow)?;
+            Ok(next(r[c]))
+        }),
+        // constant result, eg. seti, or an absolute jump
+        (Val::Imm(a), Val::Imm(b)) => {
+            let val = f(a, b);
+            let next_ip = val.map(next);
+            Box::new(move |r| {
+                r[c] = val.ok_or(Fault::Overflow)?;
+                next_ip.ok_or(Fault::Overflow)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the day 19 example
+    const DAY19: &str = "#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5
+";
+
+    // a day 21 program, it halts once r0 matches the value computed in r3
+    const DAY21: &str = "#ip 4
+seti 123 0 3
+bani 3 456 3
+eqri 3 72 3
+addr 3 4 4
+seti 0 0 4
+seti 0 0 3
+bori 3 65536 2
+seti 10552971 1 3
+bani 2 255 1
+addr 3 1 3
+bani 3 16777215 3
+muli 3 65899 3
+bani 3 16777215 3
+gtir 256 2 1
+addr 1 4 4
+addi 4 1 4
+seti 27 4 4
+seti 0 7 1
+addi 1 1 5
+muli 5 256 5
+gtrr 5 2 5
+addr 5 4 4
+addi 4 1 4
+seti 25 1 4
+addi 1 1 1
+seti 17 0 4
+setr 1 4 2
+seti 7 3 4
+eqrr 3 0 1
+addr 1 4 4
+seti 5 8 4
+";
+
+    fn both(program: &Program, r0: u64, budget: Budget) -> (Execution, Execution) {
+        let compiled = Compiled::new(program, Mode::default());
+
+        let mut vm = Machine::new();
+        vm.registers[0] = r0;
+        let interpreted = program.execute(&mut vm, budget);
+
+        let mut vm = Machine::new();
+        vm.registers[0] = r0;
+        (interpreted, compiled.execute(&mut vm, budget))
+    }
+
+    #[test]
+    fn day19_example() {
+        let program = Program::from_reader(DAY19.as_bytes());
+        let (interpreted, compiled) = both(&program, 0, Budget::Unlimited);
+
+        assert_eq!(interpreted.outcome, Outcome::Halted);
+        assert_eq!(interpreted.registers[0], 6);
+        assert_eq!(compiled, interpreted);
+    }
+
+    #[test]
+    fn day21_halting() {
+        let program = Program::from_reader(DAY21.as_bytes());
+        let (interpreted, compiled) = both(&program, 103548, Budget::Unlimited);
+
+        assert_eq!(interpreted.outcome, Outcome::Halted);
+        assert_eq!(compiled, interpreted);
+    }
+
+    #[test]
+    fn day21_budget() {
+        let program = Program::from_reader(DAY21.as_bytes());
+        for &max in &[0, 1, 7, 1000, 123_457] {
+            let (interpreted, compiled) = both(&program, 0, Budget::Instructions(max));
+
+            assert_eq!(interpreted.outcome, Outcome::BudgetExhausted);
+            assert_eq!(interpreted.n_exec, max);
+            assert_eq!(compiled, interpreted);
+        }
+    }
+}
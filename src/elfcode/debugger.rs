@@ -0,0 +1,256 @@
+use super::{Machine, Outcome, Program};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufRead, BufWriter, Write},
+};
+
+const HELP: &str = "commands:
+  s, step [n]          execute n instructions (default 1)
+  c, continue          run until a breakpoint, a watchpoint or the end of the program
+  b, break <ip>        toggle a breakpoint on ip
+  w, watch <r> [val]   stop when register r changes, or when it takes the value val
+  unwatch <r>          remove all watchpoints on register r
+  r, regs              show registers
+  i, info              show instruction counts
+  l, list              show the program
+  t, trace <file|off>  write executed instructions to a file
+  q, quit              leave the debugger";
+
+enum Watch {
+    Change(usize),
+    Value(usize, u64),
+}
+
+enum Stop {
+    Step,
+    Breakpoint,
+    Watchpoint(usize),
+    Halt(Outcome),
+}
+
+pub struct Debugger<'a> {
+    program: &'a Program,
+    vm: Machine,
+    ip: usize,
+    breakpoints: BTreeSet<usize>,
+    watches: Vec<Watch>,
+    counts: Vec<u64>,
+    n_exec: u64,
+    trace: Option<BufWriter<File>>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(program: &'a Program, vm: Machine) -> Self {
+        Self {
+            program,
+            vm,
+            ip: 0,
+            breakpoints: BTreeSet::new(),
+            watches: vec![],
+            counts: vec![0; program.instructions.len()],
+            n_exec: 0,
+            trace: None,
+        }
+    }
+
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        writeln!(output, "{}", HELP)?;
+        self.show_next(&mut output)?;
+        write!(output, "> ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let words = line.split_whitespace().collect::<Vec<_>>();
+
+            match words.as_slice() {
+                [] => {}
+                ["s"] | ["step"] => self.cmd_step(1, &mut output)?,
+                ["s", n] | ["step", n] => match n.parse() {
+                    Ok(n) => self.cmd_step(n, &mut output)?,
+                    Err(_) => writeln!(output, "invalid count: {}", n)?,
+                },
+                ["c"] | ["continue"] => self.cmd_continue(&mut output)?,
+                ["b", ip] | ["break", ip] => match ip.parse() {
+                    Ok(ip) => self.cmd_break(ip, &mut output)?,
+                    Err(_) => writeln!(output, "invalid ip: {}", ip)?,
+                },
+                ["w", r] | ["watch", r] => match self.parse_reg(r) {
+                    Some(r) => self.watches.push(Watch::Change(r)),
+                    None => writeln!(output, "invalid register: {}", r)?,
+                },
+                ["w", r, val] | ["watch", r, val] => match (self.parse_reg(r), val.parse()) {
+                    (Some(r), Ok(val)) => self.watches.push(Watch::Value(r, val)),
+                    _ => writeln!(output, "invalid watchpoint: {} {}", r, val)?,
+                },
+                ["unwatch", r] => match self.parse_reg(r) {
+                    Some(r) => self.watches.retain(|w| match *w {
+                        Watch::Change(wr) | Watch::Value(wr, _) => wr != r,
+                    }),
+                    None => writeln!(output, "invalid register: {}", r)?,
+                },
+                ["r"] | ["regs"] => self.show_registers(&mut output)?,
+                ["i"] | ["info"] => self.show_counts(&mut output)?,
+                ["l"] | ["list"] => self.show_program(&mut output)?,
+                ["t", "off"] | ["trace", "off"] => {
+                    if let Some(mut trace) = self.trace.take() {
+                        trace.flush()?;
+                    }
+                }
+                ["t", path] | ["trace", path] => match File::create(path) {
+                    Ok(f) => self.trace = Some(BufWriter::new(f)),
+                    Err(e) => writeln!(output, "unable to create {}: {}", path, e)?,
+                },
+                ["q"] | ["quit"] => break,
+                _ => writeln!(output, "{}", HELP)?,
+            }
+
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+
+        if let Some(trace) = self.trace.as_mut() {
+            trace.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn parse_reg(&self, s: &str) -> Option<usize> {
+        let s = s.trim_start_matches('r');
+        s.parse().ok().filter(|&r| r < self.vm.registers.len())
+    }
+
+    fn cmd_step<W: Write>(&mut self, n: usize, output: &mut W) -> io::Result<()> {
+        for _ in 0..n {
+            match self.exec_one()? {
+                Stop::Step => {}
+                stop => return self.report(stop, output),
+            }
+        }
+
+        self.show_next(output)
+    }
+
+    fn cmd_continue<W: Write>(&mut self, output: &mut W) -> io::Result<()> {
+        // always execute the current instruction, even if it has a breakpoint
+        let mut stop = self.exec_one()?;
+        while let Stop::Step = stop {
+            stop = if self.breakpoints.contains(&self.ip) {
+                Stop::Breakpoint
+            } else {
+                self.exec_one()?
+            };
+        }
+
+        self.report(stop, output)
+    }
+
+    fn cmd_break<W: Write>(&mut self, ip: usize, output: &mut W) -> io::Result<()> {
+        if self.breakpoints.remove(&ip) {
+            writeln!(output, "breakpoint removed at ip={}", ip)
+        } else {
+            self.breakpoints.insert(ip);
+            writeln!(output, "breakpoint set at ip={}", ip)
+        }
+    }
+
+    fn exec_one(&mut self) -> io::Result<Stop> {
+        let before = self.vm.registers;
+
+        let next_ip = match self.program.step(&mut self.vm, self.ip) {
+            Ok(next_ip) => next_ip,
+            Err(outcome) => return Ok(Stop::Halt(outcome)),
+        };
+
+        if let Some(trace) = self.trace.as_mut() {
+            writeln!(
+                trace,
+                "ip={} {:?} {} {:?}",
+                self.ip, before, self.program.instructions[self.ip], self.vm.registers
+            )?;
+        }
+
+        self.counts[self.ip] += 1;
+        self.n_exec += 1;
+        self.ip = next_ip;
+
+        let triggered = self.watches.iter().find_map(|w| match *w {
+            Watch::Change(r) if before[r] != self.vm.registers[r] => Some(r),
+            Watch::Value(r, val) if before[r] != val && self.vm.registers[r] == val => Some(r),
+            _ => None,
+        });
+
+        Ok(match triggered {
+            Some(r) => Stop::Watchpoint(r),
+            None => Stop::Step,
+        })
+    }
+
+    fn report<W: Write>(&self, stop: Stop, output: &mut W) -> io::Result<()> {
+        match stop {
+            Stop::Step => {}
+            Stop::Breakpoint => writeln!(output, "breakpoint reached")?,
+            Stop::Watchpoint(r) => {
+                writeln!(output, "watchpoint: r{} = {}", r, self.vm.registers[r])?
+            }
+            Stop::Halt(outcome) => {
+                writeln!(
+                    output,
+                    "program stopped after {} instructions: {}",
+                    self.n_exec, outcome
+                )?;
+                return self.show_registers(output);
+            }
+        }
+
+        self.show_next(output)
+    }
+
+    fn show_next<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        match self.program.instructions.get(self.ip) {
+            Some(inst) => writeln!(output, "ip={} {:?} {}", self.ip, self.vm.registers, inst),
+            None => writeln!(output, "ip={} {:?} <end>", self.ip, self.vm.registers),
+        }
+    }
+
+    fn show_registers<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        for (i, val) in self.vm.registers.iter().enumerate() {
+            let ip_marker = if i == self.program.ip_register {
+                " (ip)"
+            } else {
+                ""
+            };
+            writeln!(output, "r{} = {}{}", i, val, ip_marker)?;
+        }
+        Ok(())
+    }
+
+    fn show_counts<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        for (ip, (inst, n)) in self
+            .program
+            .instructions
+            .iter()
+            .zip(self.counts.iter())
+            .enumerate()
+        {
+            writeln!(output, "{:>4} {:<20} {}", ip, inst.to_string(), n)?;
+        }
+        writeln!(output, "total: {}", self.n_exec)
+    }
+
+    fn show_program<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "#ip {}", self.program.ip_register)?;
+        for (ip, inst) in self.program.instructions.iter().enumerate() {
+            let bp = if self.breakpoints.contains(&ip) {
+                '*'
+            } else {
+                ' '
+            };
+            let cur = if ip == self.ip { '>' } else { ' ' };
+            writeln!(output, "{}{} {:>4} {}", bp, cur, ip, inst)?;
+        }
+        Ok(())
+    }
+}
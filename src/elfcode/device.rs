@@ -0,0 +1,215 @@
+use super::{Fault, Inst, Mode};
+
+// the device of day 16: 4 registers, no ip register, and its own implementation
+// of the opcodes, which the other implementations are checked against
+
+pub const DEVICE_INSTS: [DeviceInst; 16] = [
+    DeviceInst::Addr,
+    DeviceInst::Addi,
+    DeviceInst::Mulr,
+    DeviceInst::Muli,
+    DeviceInst::Banr,
+    DeviceInst::Bani,
+    DeviceInst::Borr,
+    DeviceInst::Bori,
+    DeviceInst::Setr,
+    DeviceInst::Seti,
+    DeviceInst::Gtir,
+    DeviceInst::Gtri,
+    DeviceInst::Gtrr,
+    DeviceInst::Eqir,
+    DeviceInst::Eqri,
+    DeviceInst::Eqrr,
+];
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum DeviceInst {
+    Addr,
+    Addi,
+    Mulr,
+    Muli,
+    Banr,
+    Bani,
+    Borr,
+    Bori,
+    Setr,
+    Seti,
+    Gtir,
+    Gtri,
+    Gtrr,
+    Eqir,
+    Eqri,
+    Eqrr,
+}
+
+pub struct Device {
+    pub registers: [u64; 4],
+    mode: Mode,
+}
+
+impl Device {
+    pub fn load(values: (u64, u64, u64, u64)) -> Self {
+        Device {
+            registers: [values.0, values.1, values.2, values.3],
+            mode: Mode::default(),
+        }
+    }
+
+    pub fn values(&self) -> (u64, u64, u64, u64) {
+        (
+            self.registers[0],
+            self.registers[1],
+            self.registers[2],
+            self.registers[3],
+        )
+    }
+
+    pub fn exec(&mut self, (r0, r1, r2, r3): (DeviceInst, u8, u8, u8)) -> Result<(), Fault> {
+        match r0 {
+            DeviceInst::Addr => self.addr((0, r1, r2, r3)),
+            DeviceInst::Addi => self.addi((0, r1, r2, r3)),
+            DeviceInst::Mulr => self.mulr((0, r1, r2, r3)),
+            DeviceInst::Muli => self.muli((0, r1, r2, r3)),
+            DeviceInst::Banr => self.banr((0, r1, r2, r3)),
+            DeviceInst::Bani => self.bani((0, r1, r2, r3)),
+            DeviceInst::Borr => self.borr((0, r1, r2, r3)),
+            DeviceInst::Bori => self.bori((0, r1, r2, r3)),
+            DeviceInst::Setr => self.setr((0, r1, r2, r3)),
+            DeviceInst::Seti => self.seti((0, r1, r2, r3)),
+            DeviceInst::Gtir => self.gtir((0, r1, r2, r3)),
+            DeviceInst::Gtri => self.gtri((0, r1, r2, r3)),
+            DeviceInst::Gtrr => self.gtrr((0, r1, r2, r3)),
+            DeviceInst::Eqir => self.eqir((0, r1, r2, r3)),
+            DeviceInst::Eqri => self.eqri((0, r1, r2, r3)),
+            DeviceInst::Eqrr => self.eqrr((0, r1, r2, r3)),
+        }
+    }
+
+    fn addr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.mode.add(self.reg(r1), self.reg(r2))?;
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn addi(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.mode.add(self.reg(r1), v2 as u64)?;
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn mulr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.mode.mul(self.reg(r1), self.reg(r2))?;
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn muli(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.mode.mul(self.reg(r1), v2 as u64)?;
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn banr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.reg(r1) & self.reg(r2);
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn bani(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.reg(r1) & v2 as u64;
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn borr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.mode.fit(u128::from(self.reg(r1) | self.reg(r2)))?;
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn bori(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.mode.fit(u128::from(self.reg(r1) | v2 as u64))?;
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn setr(&mut self, (_, r1, _, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.reg(r1);
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn seti(&mut self, (_, v1, _, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = self.mode.fit(u128::from(v1))?;
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn gtir(&mut self, (_, v1, r2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = if v1 as u64 > self.reg(r2) { 1 } else { 0 };
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn gtri(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = if self.reg(r1) > v2 as u64 { 1 } else { 0 };
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn gtrr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = if self.reg(r1) > self.reg(r2) { 1 } else { 0 };
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn eqir(&mut self, (_, v1, r2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = if v1 as u64 == self.reg(r2) { 1 } else { 0 };
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn eqri(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = if self.reg(r1) == v2 as u64 { 1 } else { 0 };
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn eqrr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) -> Result<(), Fault> {
+        let val = if self.reg(r1) == self.reg(r2) { 1 } else { 0 };
+        self.set_reg(r3, val);
+        Ok(())
+    }
+
+    fn reg(&self, n: u8) -> u64 {
+        self.registers[n as usize]
+    }
+
+    fn set_reg(&mut self, n: u8, val: u64) {
+        self.registers[n as usize] = val;
+    }
+}
+
+// runs elfcode instructions on the device, to check it against the other implementations,
+// arguments must fit in a u8 and registers be below 4
+// returns the registers and the position of the failing instruction, if any
+pub fn run_on_device(
+    instructions: &[Inst],
+    registers: [u64; 4],
+) -> ([u64; 4], Option<(usize, Fault)>) {
+    let mut m = Device {
+        registers,
+        mode: Mode::default(),
+    };
+
+    for (i, inst) in instructions.iter().enumerate() {
+        // both instruction sets are listed in the same order
+        let instr = DEVICE_INSTS[inst.opcode as usize];
+        let [a, b, c] = inst.args;
+        if let Err(f) = m.exec((instr, a as u8, b as u8, c as u8)) {
+            return (m.registers, Some((i, f)));
+        }
+    }
+
+    (m.registers, None)
+}
@@ -0,0 +1,583 @@
+mod assembler;
+mod compiler;
+mod debugger;
+mod device;
+mod optimiser;
+mod profiler;
+
+pub use self::{
+    assembler::{assemble, decode, encode, parse_mapping},
+    compiler::Compiled,
+    debugger::Debugger,
+    device::{run_on_device, Device, DeviceInst, DEVICE_INSTS},
+    optimiser::optimise,
+    profiler::Profile,
+};
+
+use regex::Regex;
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead, BufReader},
+    time::{Duration, Instant},
+};
+
+pub const N_REGISTERS: usize = 6;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Opcode {
+    Addr,
+    Addi,
+    Mulr,
+    Muli,
+    Banr,
+    Bani,
+    Borr,
+    Bori,
+    Setr,
+    Seti,
+    Gtir,
+    Gtri,
+    Gtrr,
+    Eqir,
+    Eqri,
+    Eqrr,
+}
+
+impl Opcode {
+    pub const ALL: [Opcode; 16] = [
+        Opcode::Addr,
+        Opcode::Addi,
+        Opcode::Mulr,
+        Opcode::Muli,
+        Opcode::Banr,
+        Opcode::Bani,
+        Opcode::Borr,
+        Opcode::Bori,
+        Opcode::Setr,
+        Opcode::Seti,
+        Opcode::Gtir,
+        Opcode::Gtri,
+        Opcode::Gtrr,
+        Opcode::Eqir,
+        Opcode::Eqri,
+        Opcode::Eqrr,
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "addr" => Some(Opcode::Addr),
+            "addi" => Some(Opcode::Addi),
+            "mulr" => Some(Opcode::Mulr),
+            "muli" => Some(Opcode::Muli),
+            "banr" => Some(Opcode::Banr),
+            "bani" => Some(Opcode::Bani),
+            "borr" => Some(Opcode::Borr),
+            "bori" => Some(Opcode::Bori),
+            "setr" => Some(Opcode::Setr),
+            "seti" => Some(Opcode::Seti),
+            "gtir" => Some(Opcode::Gtir),
+            "gtri" => Some(Opcode::Gtri),
+            "gtrr" => Some(Opcode::Gtrr),
+            "eqir" => Some(Opcode::Eqir),
+            "eqri" => Some(Opcode::Eqri),
+            "eqrr" => Some(Opcode::Eqrr),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Opcode::Addr => "addr",
+            Opcode::Addi => "addi",
+            Opcode::Mulr => "mulr",
+            Opcode::Muli => "muli",
+            Opcode::Banr => "banr",
+            Opcode::Bani => "bani",
+            Opcode::Borr => "borr",
+            Opcode::Bori => "bori",
+            Opcode::Setr => "setr",
+            Opcode::Seti => "seti",
+            Opcode::Gtir => "gtir",
+            Opcode::Gtri => "gtri",
+            Opcode::Gtrr => "gtrr",
+            Opcode::Eqir => "eqir",
+            Opcode::Eqri => "eqri",
+            Opcode::Eqrr => "eqrr",
+        }
+    }
+
+    // which of the A and B operands are registers, C always is one
+    pub fn operands(self) -> (bool, bool) {
+        match self {
+            Opcode::Addr | Opcode::Mulr | Opcode::Banr | Opcode::Borr => (true, true),
+            Opcode::Gtrr | Opcode::Eqrr => (true, true),
+            Opcode::Addi | Opcode::Muli | Opcode::Bani | Opcode::Bori => (true, false),
+            Opcode::Gtri | Opcode::Eqri | Opcode::Setr => (true, false),
+            Opcode::Gtir | Opcode::Eqir => (false, true),
+            Opcode::Seti => (false, false),
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Inst {
+    pub opcode: Opcode,
+    pub args: [u32; 3],
+}
+
+impl Inst {
+    // false if the instruction refers to a register that does not exist
+    pub fn has_valid_registers(&self) -> bool {
+        let (a_reg, b_reg) = self.opcode.operands();
+        let valid = |arg: u32, is_reg: bool| !is_reg || (arg as usize) < N_REGISTERS;
+        valid(self.args[0], a_reg) && valid(self.args[1], b_reg) && valid(self.args[2], true)
+    }
+}
+
+impl Display for Inst {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.opcode, self.args[0], self.args[1], self.args[2]
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Budget {
+    Unlimited,
+    Instructions(u64),
+    Time(Duration),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    // ip points outside of the program
+    Halted,
+    BudgetExhausted,
+    InvalidRegister { ip: usize, register: u32 },
+    Overflow { ip: usize },
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Outcome::Halted => write!(f, "halted"),
+            Outcome::BudgetExhausted => write!(f, "budget exhausted"),
+            Outcome::InvalidRegister { ip, register } => {
+                write!(f, "invalid register {} at ip={}", register, ip)
+            }
+            Outcome::Overflow { ip } => write!(f, "arithmetic overflow at ip={}", ip),
+        }
+    }
+}
+
+// errors raised by a single instruction, which does not know its ip
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Fault {
+    InvalidRegister(u32),
+    Overflow,
+}
+
+impl Fault {
+    fn at(self, ip: usize) -> Outcome {
+        match self {
+            Fault::InvalidRegister(register) => Outcome::InvalidRegister { ip, register },
+            Fault::Overflow => Outcome::Overflow { ip },
+        }
+    }
+}
+
+// what happens when the result of an operation does not fit in a register
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Arithmetic {
+    // the program stops with an overflow error
+    Checked,
+    Wrapping,
+    Saturating,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Width {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl Width {
+    pub fn max(self) -> u64 {
+        match self {
+            Width::U8 => u8::MAX as u64,
+            Width::U16 => u16::MAX as u64,
+            Width::U32 => u32::MAX as u64,
+            Width::U64 => u64::MAX,
+        }
+    }
+}
+
+// how the machine computes, the default is 64 bit registers with checked arithmetic
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Mode {
+    pub arithmetic: Arithmetic,
+    pub width: Width,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Self {
+            arithmetic: Arithmetic::Checked,
+            width: Width::U64,
+        }
+    }
+}
+
+impl Mode {
+    // parses an arithmetic followed by an optional width, eg. "wrapping32" or "checked"
+    pub fn parse(s: &str) -> Option<Self> {
+        let pos = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        let arithmetic = match &s[..pos] {
+            "checked" => Arithmetic::Checked,
+            "wrapping" => Arithmetic::Wrapping,
+            "saturating" => Arithmetic::Saturating,
+            _ => return None,
+        };
+        let width = match &s[pos..] {
+            "8" => Width::U8,
+            "16" => Width::U16,
+            "32" => Width::U32,
+            "64" | "" => Width::U64,
+            _ => return None,
+        };
+
+        Some(Self { arithmetic, width })
+    }
+
+    // brings the exact result of an operation back into the range of a register
+    pub fn fit(self, val: u128) -> Result<u64, Fault> {
+        let max = self.width.max();
+        if val <= u128::from(max) {
+            return Ok(val as u64);
+        }
+
+        match self.arithmetic {
+            Arithmetic::Checked => Err(Fault::Overflow),
+            Arithmetic::Wrapping => Ok(val as u64 & max),
+            Arithmetic::Saturating => Ok(max),
+        }
+    }
+
+    // the exact result is only needed when the fast path overflows
+    pub fn add(self, a: u64, b: u64) -> Result<u64, Fault> {
+        match a.checked_add(b) {
+            Some(val) if val <= self.width.max() => Ok(val),
+            _ => self.fit(u128::from(a) + u128::from(b)),
+        }
+    }
+
+    pub fn mul(self, a: u64, b: u64) -> Result<u64, Fault> {
+        match a.checked_mul(b) {
+            Some(val) if val <= self.width.max() => Ok(val),
+            _ => self.fit(u128::from(a) * u128::from(b)),
+        }
+    }
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let arithmetic = match self.arithmetic {
+            Arithmetic::Checked => "checked",
+            Arithmetic::Wrapping => "wrapping",
+            Arithmetic::Saturating => "saturating",
+        };
+        let bits = 64 - self.width.max().leading_zeros();
+        write!(f, "{}{}", arithmetic, bits)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Execution {
+    pub outcome: Outcome,
+    pub n_exec: u64,
+    pub registers: [u64; N_REGISTERS],
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Program {
+    pub ip_register: usize,
+    pub instructions: Vec<Inst>,
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "#ip {}", self.ip_register)?;
+        for inst in self.instructions.iter() {
+            writeln!(f, "{}", inst)?;
+        }
+        Ok(())
+    }
+}
+
+impl Program {
+    pub fn from_reader<R: io::Read>(r: R) -> Program {
+        let mut lines = BufReader::new(r).lines();
+
+        // parse first line
+        let ip_register = lines
+            .next()
+            .and_then(|s| s.ok())
+            .and_then(|s| s.chars().nth(4))
+            .and_then(|c| c.to_digit(10))
+            .unwrap_or(0) as usize;
+
+        // parse instructions
+        let inst_re = Regex::new(r"(\w+) (\d+) (\d+) (\d+)").unwrap();
+        let instructions = lines
+            .map_while(Result::ok)
+            .filter_map(|s| {
+                inst_re.captures(s.as_str()).and_then(|c| {
+                    Some(Inst {
+                        opcode: Opcode::parse(c.get(1)?.as_str())?,
+                        args: [
+                            c.get(2)?.as_str().parse().ok()?,
+                            c.get(3)?.as_str().parse().ok()?,
+                            c.get(4)?.as_str().parse().ok()?,
+                        ],
+                    })
+                })
+            })
+            .collect();
+
+        Program {
+            ip_register,
+            instructions,
+        }
+    }
+
+    pub fn execute(&self, vm: &mut Machine, budget: Budget) -> Execution {
+        self.execute_with(vm, budget, |_, _| {})
+    }
+
+    // same as execute, but calls on_step with the ip of every executed instruction and the next ip
+    pub fn execute_with<F>(&self, vm: &mut Machine, budget: Budget, on_step: F) -> Execution
+    where
+        F: FnMut(usize, usize),
+    {
+        run(vm, budget, |vm, ip| self.step(vm, ip), on_step)
+    }
+
+    // executes the instruction at ip, and returns the next value of ip,
+    // or the reason why the program stopped
+    pub fn step(&self, vm: &mut Machine, ip: usize) -> Result<usize, Outcome> {
+        let inst = self.instructions.get(ip).ok_or(Outcome::Halted)?;
+
+        // prepare ip register
+        let ip_register = self.ip_register as u32;
+        vm.set_reg(ip_register, ip as u64).map_err(|f| f.at(ip))?;
+
+        // exec
+        vm.exec(inst).map_err(|f| f.at(ip))?;
+
+        // restore ip and increment for next instruction
+        let next_ip = vm.reg(ip_register).map_err(|f| f.at(ip))?;
+        Ok(next_ip_from(next_ip))
+    }
+
+    // true if the instruction at ip is a jump, eg. it writes the ip register
+    pub fn writes_ip(&self, ip: usize) -> bool {
+        self.instructions
+            .get(ip)
+            .is_some_and(|inst| inst.args[2] as usize == self.ip_register)
+    }
+}
+
+// value of ip after an instruction left val in the ip register,
+// anything too large is out of the program anyway
+fn next_ip_from(val: u64) -> usize {
+    usize::try_from(val)
+        .ok()
+        .and_then(|ip| ip.checked_add(1))
+        .unwrap_or(usize::MAX)
+}
+
+// fetch/execute loop shared by the interpreter and the compiled programs
+fn run<S, F>(vm: &mut Machine, budget: Budget, mut step: S, mut on_step: F) -> Execution
+where
+    S: FnMut(&mut Machine, usize) -> Result<usize, Outcome>,
+    F: FnMut(usize, usize),
+{
+    let start = Instant::now();
+    let mut n_exec = 0;
+    let mut ip = 0;
+
+    let outcome = loop {
+        let exhausted = match budget {
+            Budget::Unlimited => false,
+            Budget::Instructions(max) => n_exec >= max,
+            // only check the clock from time to time, it is much slower than an instruction
+            Budget::Time(max) => n_exec % 1024 == 0 && start.elapsed() >= max,
+        };
+        if exhausted {
+            break Outcome::BudgetExhausted;
+        }
+
+        match step(vm, ip) {
+            Ok(next_ip) => {
+                on_step(ip, next_ip);
+                ip = next_ip;
+            }
+            Err(outcome) => break outcome,
+        }
+        n_exec += 1;
+    };
+
+    Execution {
+        outcome,
+        n_exec,
+        registers: vm.registers,
+    }
+}
+
+#[derive(Clone)]
+pub struct Machine {
+    pub registers: [u64; N_REGISTERS],
+    pub mode: Mode,
+}
+
+impl Machine {
+    pub fn new() -> Self {
+        Self::with_mode(Mode::default())
+    }
+
+    pub fn with_mode(mode: Mode) -> Self {
+        Self {
+            registers: [0; N_REGISTERS],
+            mode,
+        }
+    }
+
+    pub fn exec(&mut self, inst: &Inst) -> Result<(), Fault> {
+        match inst.opcode {
+            Opcode::Addr => self.addr(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Addi => self.addi(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Mulr => self.mulr(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Muli => self.muli(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Banr => self.banr(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Bani => self.bani(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Borr => self.borr(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Bori => self.bori(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Setr => self.setr(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Seti => self.seti(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Gtir => self.gtir(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Gtri => self.gtri(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Gtrr => self.gtrr(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Eqir => self.eqir(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Eqri => self.eqri(inst.args[0], inst.args[1], inst.args[2]),
+            Opcode::Eqrr => self.eqrr(inst.args[0], inst.args[1], inst.args[2]),
+        }
+    }
+
+    fn addr(&mut self, r1: u32, r2: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.mode.add(self.reg(r1)?, self.reg(r2)?)?;
+        self.set_reg(r3, val)
+    }
+
+    fn addi(&mut self, r1: u32, v2: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.mode.add(self.reg(r1)?, v2 as u64)?;
+        self.set_reg(r3, val)
+    }
+
+    fn mulr(&mut self, r1: u32, r2: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.mode.mul(self.reg(r1)?, self.reg(r2)?)?;
+        self.set_reg(r3, val)
+    }
+
+    fn muli(&mut self, r1: u32, v2: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.mode.mul(self.reg(r1)?, v2 as u64)?;
+        self.set_reg(r3, val)
+    }
+
+    fn banr(&mut self, r1: u32, r2: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.reg(r1)? & self.reg(r2)?;
+        self.set_reg(r3, val)
+    }
+
+    fn bani(&mut self, r1: u32, v2: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.reg(r1)? & v2 as u64;
+        self.set_reg(r3, val)
+    }
+
+    fn borr(&mut self, r1: u32, r2: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.mode.fit(u128::from(self.reg(r1)? | self.reg(r2)?))?;
+        self.set_reg(r3, val)
+    }
+
+    fn bori(&mut self, r1: u32, v2: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.mode.fit(u128::from(self.reg(r1)? | v2 as u64))?;
+        self.set_reg(r3, val)
+    }
+
+    fn setr(&mut self, r1: u32, _: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.reg(r1)?;
+        self.set_reg(r3, val)
+    }
+
+    fn seti(&mut self, v1: u32, _: u32, r3: u32) -> Result<(), Fault> {
+        let val = self.mode.fit(u128::from(v1))?;
+        self.set_reg(r3, val)
+    }
+
+    fn gtir(&mut self, v1: u32, r2: u32, r3: u32) -> Result<(), Fault> {
+        let val = if v1 as u64 > self.reg(r2)? { 1 } else { 0 };
+        self.set_reg(r3, val)
+    }
+
+    fn gtri(&mut self, r1: u32, v2: u32, r3: u32) -> Result<(), Fault> {
+        let val = if self.reg(r1)? > v2 as u64 { 1 } else { 0 };
+        self.set_reg(r3, val)
+    }
+
+    fn gtrr(&mut self, r1: u32, r2: u32, r3: u32) -> Result<(), Fault> {
+        let val = if self.reg(r1)? > self.reg(r2)? { 1 } else { 0 };
+        self.set_reg(r3, val)
+    }
+
+    fn eqir(&mut self, v1: u32, r2: u32, r3: u32) -> Result<(), Fault> {
+        let val = if v1 as u64 == self.reg(r2)? { 1 } else { 0 };
+        self.set_reg(r3, val)
+    }
+
+    fn eqri(&mut self, r1: u32, v2: u32, r3: u32) -> Result<(), Fault> {
+        let val = if self.reg(r1)? == v2 as u64 { 1 } else { 0 };
+        self.set_reg(r3, val)
+    }
+
+    fn eqrr(&mut self, r1: u32, r2: u32, r3: u32) -> Result<(), Fault> {
+        let val = if self.reg(r1)? == self.reg(r2)? { 1 } else { 0 };
+        self.set_reg(r3, val)
+    }
+
+    fn reg(&self, n: u32) -> Result<u64, Fault> {
+        self.registers
+            .get(n as usize)
+            .cloned()
+            .ok_or(Fault::InvalidRegister(n))
+    }
+
+    fn set_reg(&mut self, n: u32, val: u64) -> Result<(), Fault> {
+        let r = self
+            .registers
+            .get_mut(n as usize)
+            .ok_or(Fault::InvalidRegister(n))?;
+        *r = val;
+        Ok(())
+    }
+}
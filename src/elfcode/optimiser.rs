@@ -0,0 +1,474 @@
+use super::{next_ip_from, Arithmetic, Inst, Mode, Opcode, Program, N_REGISTERS};
+use std::convert::TryFrom;
+
+// what an instruction does with the ip register, once analysed
+#[derive(Clone, Debug)]
+enum Op {
+    // neither reads nor writes ip
+    Plain(Inst),
+    // always continues at the given address
+    Jump(usize),
+    // continues at the next address plus the register, which holds the result of the
+    // comparison just before, eg. gtrr a b t + addr t ip ip skips an instruction if a > b
+    Skip(u32),
+    // anything else using ip, kept in place, if it is a jump
+    // it goes to the given address or further
+    Other {
+        inst: Inst,
+        min_target: Option<usize>,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Val {
+    Reg(u32),
+    Imm(u64),
+}
+
+// rewrites a program into an equivalent one:
+//  - reads of ip are replaced by the address of the instruction
+//  - constant operands are propagated inside basic blocks, eg. seti/addi chains become a seti
+//  - jumps with a constant target become seti, conditional skips become addr t ip ip
+//  - if all jump targets are known, writes overwritten before being read are removed,
+//    as are jumps to the next instruction, and the program is relocated accordingly
+//
+// the registers are the same as the original program's when it stops, except for ip,
+// as long as both run with the given arithmetic mode,
+// programs using invalid registers are left untouched
+pub fn optimise(program: &Program, mode: Mode) -> Program {
+    let ip_register = program.ip_register;
+    let valid = ip_register < N_REGISTERS
+        && !program.instructions.is_empty()
+        && program.instructions.iter().all(Inst::has_valid_registers);
+    if !valid {
+        return program.clone();
+    }
+
+    let mut ops = program
+        .instructions
+        .iter()
+        .enumerate()
+        .map(|(ip, inst)| classify(inst, ip, ip_register, mode))
+        .collect::<Vec<_>>();
+
+    check_skips(&mut ops, ip_register, mode);
+    let blocks = blocks(&ops);
+
+    for &(start, end) in blocks.iter() {
+        propagate_constants(&mut ops[start..end], start, mode);
+    }
+    thread_jumps(&mut ops);
+
+    let relocatable = !ops.iter().any(|op| matches!(op, Op::Other { .. }));
+    if !relocatable {
+        return Program {
+            ip_register,
+            instructions: ops
+                .into_iter()
+                .map(|op| emit(op, ip_register, |target| target))
+                .collect(),
+        };
+    }
+
+    // the first instruction and the ones after a skip cannot move relative to their predecessor
+    let mut pinned = vec![false; ops.len()];
+    pinned[0] = true;
+    for (ip, op) in ops.iter().enumerate() {
+        if let (Op::Skip(_), Some(p)) = (op, pinned.get_mut(ip + 1)) {
+            *p = true;
+        }
+    }
+
+    let mut removed = vec![false; ops.len()];
+    for &(start, end) in blocks.iter() {
+        remove_dead_writes(
+            &ops[start..end],
+            &pinned[start..end],
+            &mut removed[start..end],
+            mode,
+        );
+    }
+    for (ip, op) in ops.iter().enumerate() {
+        if let Op::Jump(target) = *op {
+            removed[ip] |= target == ip + 1 && !pinned[ip];
+        }
+    }
+
+    // new address of every instruction, one more for the end of the program
+    let mut addresses = vec![0; ops.len() + 1];
+    for ip in 0..ops.len() {
+        addresses[ip + 1] = addresses[ip] + if removed[ip] { 0 } else { 1 };
+    }
+    let end = ops.len();
+    let relocate = |target: usize| addresses[target.min(end)];
+
+    Program {
+        ip_register,
+        instructions: ops
+            .into_iter()
+            .zip(removed)
+            .filter(|&(_, removed)| !removed)
+            .map(|(op, _)| emit(op, ip_register, relocate))
+            .collect(),
+    }
+}
+
+fn classify(inst: &Inst, ip: usize, ip_register: usize, mode: Mode) -> Op {
+    let (a, b) = operands(inst, |r| {
+        if r as usize == ip_register {
+            Some(ip as u64)
+        } else {
+            None
+        }
+    });
+    let c = inst.args[2];
+    let rebuilt = if is_substituted(inst, a, b) {
+        rebuild(inst.opcode, a, b, c, mode)
+    } else {
+        Some(inst.clone())
+    };
+
+    if c as usize != ip_register {
+        return match rebuilt {
+            Some(inst) => Op::Plain(inst),
+            // a constant too large for an immediate value
+            None => Op::Other {
+                inst: inst.clone(),
+                min_target: None,
+            },
+        };
+    }
+
+    if let Some(val) = constant(inst.opcode, a, b, mode) {
+        return Op::Jump(next_ip_from(val));
+    }
+
+    let is_add = matches!(inst.opcode, Opcode::Addr | Opcode::Addi);
+    let inst = rebuilt.unwrap_or_else(|| inst.clone());
+    match (a, b) {
+        // relative jumps, the next address must not overflow for a skip
+        (Val::Reg(t), Val::Imm(k)) | (Val::Imm(k), Val::Reg(t)) if is_add => {
+            if k == ip as u64 && k < mode.width.max() {
+                Op::Skip(t)
+            } else {
+                Op::Other {
+                    inst,
+                    min_target: Some(min_target(k, mode)),
+                }
+            }
+        }
+        _ => Op::Other {
+            inst,
+            min_target: Some(0),
+        },
+    }
+}
+
+// a skip is only recognised if its register has just been set by a comparison,
+// which means it cannot be reached by a jump
+fn check_skips(ops: &mut [Op], ip_register: usize, mode: Mode) {
+    loop {
+        let targets = targets(ops);
+        let invalid = ops.iter().enumerate().position(|(ip, op)| match *op {
+            Op::Skip(t) => {
+                let after_comparison = ip > 0
+                    && match &ops[ip - 1] {
+                        Op::Plain(inst) => inst.args[2] == t && is_comparison(inst.opcode),
+                        _ => false,
+                    };
+                !after_comparison || targets[ip]
+            }
+            _ => false,
+        });
+
+        match invalid {
+            Some(ip) => {
+                if let Op::Skip(t) = ops[ip] {
+                    ops[ip] = Op::Other {
+                        inst: Inst {
+                            opcode: Opcode::Addi,
+                            args: [t, ip as u32, ip_register as u32],
+                        },
+                        min_target: Some(min_target(ip as u64, mode)),
+                    };
+                }
+            }
+            None => break,
+        }
+    }
+}
+
+// lowest address reached by a jump to a register plus k, wrapping can go anywhere
+fn min_target(k: u64, mode: Mode) -> usize {
+    match mode.arithmetic {
+        Arithmetic::Checked => next_ip_from(k),
+        Arithmetic::Saturating => next_ip_from(k.min(mode.width.max())),
+        Arithmetic::Wrapping => 0,
+    }
+}
+
+// instructions that can be reached by a jump
+fn targets(ops: &[Op]) -> Vec<bool> {
+    let mut targets = vec![false; ops.len() + 2];
+
+    for (ip, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Plain(_)
+            | Op::Other {
+                min_target: None, ..
+            } => {}
+            Op::Jump(target) => targets[target.min(ops.len())] = true,
+            Op::Skip(_) => {
+                targets[ip + 1] = true;
+                targets[ip + 2] = true;
+            }
+            Op::Other {
+                min_target: Some(min),
+                ..
+            } => {
+                for t in targets.iter_mut().skip(min) {
+                    *t = true;
+                }
+            }
+        }
+    }
+
+    targets.truncate(ops.len());
+    targets
+}
+
+// basic blocks, as [start, end) ranges
+fn blocks(ops: &[Op]) -> Vec<(usize, usize)> {
+    let targets = targets(ops);
+    let mut blocks = vec![];
+    let mut start = 0;
+
+    for (ip, op) in ops.iter().enumerate() {
+        let is_jump = match op {
+            Op::Plain(_) => false,
+            Op::Other { min_target, .. } => min_target.is_some(),
+            _ => true,
+        };
+        if is_jump || targets.get(ip + 1).cloned().unwrap_or(true) {
+            blocks.push((start, ip + 1));
+            start = ip + 1;
+        }
+    }
+
+    blocks
+}
+
+fn propagate_constants(block: &mut [Op], start: usize, mode: Mode) {
+    let mut known = [None; N_REGISTERS];
+
+    for (i, op) in block.iter_mut().enumerate() {
+        match op {
+            Op::Plain(inst) => {
+                let (a, b) = operands(inst, |r| known[r as usize]);
+                let c = inst.args[2];
+                let val = constant(inst.opcode, a, b, mode);
+
+                // only rewrite the instruction when something changed
+                let (imm_a, imm_b) = operands(inst, |r| known[r as usize].filter(|&v| fits(v)));
+                if is_substituted(inst, imm_a, imm_b) {
+                    if let Some(folded) = rebuild(inst.opcode, imm_a, imm_b, c, mode) {
+                        *inst = folded;
+                    }
+                }
+
+                known[c as usize] = val;
+            }
+            Op::Skip(t) => {
+                if let Some(val @ 0..=1) = known[*t as usize] {
+                    *op = Op::Jump(start + i + 1 + val as usize);
+                }
+            }
+            Op::Other { inst, .. } => known[inst.args[2] as usize] = None,
+            Op::Jump(_) => {}
+        }
+    }
+}
+
+// jumps to a jump go directly to the final target
+fn thread_jumps(ops: &mut [Op]) {
+    for ip in 0..ops.len() {
+        if let Op::Jump(mut target) = ops[ip] {
+            let mut hops = 0;
+            while let Some(&Op::Jump(next)) = ops.get(target) {
+                if next == target || hops == ops.len() {
+                    break;
+                }
+                target = next;
+                hops += 1;
+            }
+            ops[ip] = Op::Jump(target);
+        }
+    }
+}
+
+// registers are all considered live at the end of a block, and before any instruction
+// which could overflow, as the program would stop there
+fn remove_dead_writes(block: &[Op], pinned: &[bool], removed: &mut [bool], mode: Mode) {
+    let mut live = [true; N_REGISTERS];
+
+    for (i, op) in block.iter().enumerate().rev() {
+        match op {
+            Op::Plain(inst) => {
+                let c = inst.args[2] as usize;
+                let may_fail = may_overflow(inst, mode);
+                if !live[c] && !may_fail && !pinned[i] {
+                    removed[i] = true;
+                    continue;
+                }
+
+                live[c] = false;
+                let (a, b) = operands(inst, |_| None);
+                for val in [a, b].iter() {
+                    if let Val::Reg(r) = *val {
+                        live[r as usize] = true;
+                    }
+                }
+                if may_fail {
+                    live = [true; N_REGISTERS];
+                }
+            }
+            Op::Skip(t) => live[*t as usize] = true,
+            _ => live = [true; N_REGISTERS],
+        }
+    }
+}
+
+fn emit<F: Fn(usize) -> usize>(op: Op, ip_register: usize, relocate: F) -> Inst {
+    let ip_register = ip_register as u32;
+
+    match op {
+        Op::Plain(inst) | Op::Other { inst, .. } => inst,
+        // the ip register is incremented after the jump, jumps too far away all end the program
+        Op::Jump(target) => Inst {
+            opcode: Opcode::Seti,
+            args: [
+                u32::try_from(relocate(target) - 1).unwrap_or(u32::MAX),
+                0,
+                ip_register,
+            ],
+        },
+        Op::Skip(t) => Inst {
+            opcode: Opcode::Addr,
+            args: [t, ip_register, ip_register],
+        },
+    }
+}
+
+fn is_comparison(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr | Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr
+    )
+}
+
+fn fits(val: u64) -> bool {
+    u32::try_from(val).is_ok()
+}
+
+// operands A and B, registers are replaced by the value given by known, if any
+fn operands<F: Fn(u32) -> Option<u64>>(inst: &Inst, known: F) -> (Val, Val) {
+    let (a_reg, b_reg) = inst.opcode.operands();
+    let val = |arg: u32, is_reg: bool| {
+        if is_reg {
+            known(arg).map_or(Val::Reg(arg), Val::Imm)
+        } else {
+            Val::Imm(arg as u64)
+        }
+    };
+
+    let b = match inst.opcode {
+        // B is ignored
+        Opcode::Setr | Opcode::Seti => Val::Imm(0),
+        _ => val(inst.args[1], b_reg),
+    };
+
+    (val(inst.args[0], a_reg), b)
+}
+
+fn is_substituted(inst: &Inst, a: Val, b: Val) -> bool {
+    let (a_reg, b_reg) = inst.opcode.operands();
+    matches!((a_reg, a), (true, Val::Imm(_))) || matches!((b_reg, b), (true, Val::Imm(_)))
+}
+
+fn may_overflow(inst: &Inst, mode: Mode) -> bool {
+    let max = mode.width.max();
+    mode.arithmetic == Arithmetic::Checked
+        && match inst.opcode {
+            Opcode::Addr | Opcode::Addi | Opcode::Mulr | Opcode::Muli => true,
+            Opcode::Seti => u64::from(inst.args[0]) > max,
+            Opcode::Bori => u64::from(inst.args[1]) > max,
+            _ => false,
+        }
+}
+
+// value of the result, if known, the B operand of setr and seti is always an immediate
+fn constant(opcode: Opcode, a: Val, b: Val, mode: Mode) -> Option<u64> {
+    match (a, b) {
+        (Val::Imm(a), Val::Imm(b)) => eval(opcode, a, b, mode),
+        _ => None,
+    }
+}
+
+// None on overflow in checked mode
+fn eval(opcode: Opcode, a: u64, b: u64, mode: Mode) -> Option<u64> {
+    match opcode {
+        Opcode::Addr | Opcode::Addi => mode.add(a, b).ok(),
+        Opcode::Mulr | Opcode::Muli => mode.mul(a, b).ok(),
+        Opcode::Banr | Opcode::Bani => Some(a & b),
+        Opcode::Borr | Opcode::Bori => mode.fit(u128::from(a | b)).ok(),
+        Opcode::Setr => Some(a),
+        Opcode::Seti => mode.fit(u128::from(a)).ok(),
+        Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr => Some(u64::from(a > b)),
+        Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr => Some(u64::from(a == b)),
+    }
+}
+
+// builds an instruction doing the same as opcode with the given operands,
+// None if an immediate value does not fit
+fn rebuild(opcode: Opcode, a: Val, b: Val, c: u32, mode: Mode) -> Option<Inst> {
+    let imm = |v: u64| u32::try_from(v).ok();
+
+    if let Some(val) = constant(opcode, a, b, mode) {
+        // seti would not set a value out of range, eg. the address from a setr ip
+        if val > mode.width.max() {
+            return None;
+        }
+        return Some(Inst {
+            opcode: Opcode::Seti,
+            args: [imm(val)?, 0, c],
+        });
+    }
+
+    // variants for register/register, register/immediate and immediate/register operands,
+    // the last one being missing for commutative operations
+    let (rr, ri, ir) = match opcode {
+        Opcode::Addr | Opcode::Addi => (Opcode::Addr, Opcode::Addi, None),
+        Opcode::Mulr | Opcode::Muli => (Opcode::Mulr, Opcode::Muli, None),
+        Opcode::Banr | Opcode::Bani => (Opcode::Banr, Opcode::Bani, None),
+        Opcode::Borr | Opcode::Bori => (Opcode::Borr, Opcode::Bori, None),
+        Opcode::Setr | Opcode::Seti => (Opcode::Setr, Opcode::Setr, None),
+        Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr => {
+            (Opcode::Gtrr, Opcode::Gtri, Some(Opcode::Gtir))
+        }
+        Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr => {
+            (Opcode::Eqrr, Opcode::Eqri, Some(Opcode::Eqir))
+        }
+    };
+
+    let (opcode, args) = match (a, b) {
+        (Val::Reg(a), Val::Reg(b)) => (rr, [a, b, c]),
+        (Val::Reg(a), Val::Imm(b)) => (ri, [a, imm(b)?, c]),
+        (Val::Imm(a), Val::Reg(b)) => match ir {
+            Some(ir) => (ir, [imm(a)?, b, c]),
+            None => (ri, [b, imm(a)?, c]),
+        },
+        (Val::Imm(_), Val::Imm(_)) => return None,
+    };
+
+    Some(Inst { opcode, args })
+}
@@ -0,0 +1,116 @@
+use super::{Budget, Machine, Outcome, Program};
+use std::{
+    cmp::Reverse,
+    collections::BTreeSet,
+    io::{self, Write},
+};
+
+pub struct Profile<'a> {
+    program: &'a Program,
+    counts: Vec<u64>,
+    leaders: BTreeSet<usize>,
+    n_exec: u64,
+    outcome: Outcome,
+}
+
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub count: u64,
+}
+
+impl<'a> Profile<'a> {
+    // runs the program until it stops or its budget is exhausted
+    pub fn run(program: &'a Program, vm: &mut Machine, budget: Budget) -> Self {
+        let mut counts = vec![0; program.instructions.len()];
+        let mut leaders = BTreeSet::new();
+
+        // every instruction reached by a jump starts a basic block
+        leaders.insert(0);
+
+        let execution = program.execute_with(vm, budget, |ip, next_ip| {
+            counts[ip] += 1;
+
+            if next_ip != ip + 1 || program.writes_ip(ip) {
+                leaders.insert(next_ip);
+            }
+        });
+
+        Self {
+            program,
+            counts,
+            leaders,
+            n_exec: execution.n_exec,
+            outcome: execution.outcome,
+        }
+    }
+
+    // basic blocks end on an instruction that writes ip, or just before the next leader
+    pub fn blocks(&self) -> Vec<Block> {
+        let len = self.program.instructions.len();
+        let mut blocks = vec![];
+        let mut start = 0;
+
+        for ip in 0..len {
+            let last =
+                ip + 1 == len || self.program.writes_ip(ip) || self.leaders.contains(&(ip + 1));
+            if last {
+                blocks.push(Block {
+                    start,
+                    end: ip,
+                    count: self.counts[start],
+                });
+                start = ip + 1;
+            }
+        }
+
+        blocks
+    }
+
+    pub fn report<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        let total = self.n_exec.max(1) as f64;
+
+        writeln!(output, "instructions executed: {}", self.n_exec)?;
+        writeln!(output, "outcome: {}", self.outcome)?;
+        writeln!(output)?;
+        writeln!(output, "  ip instruction             count       %")?;
+
+        let mut by_ip = self.counts.iter().enumerate().collect::<Vec<_>>();
+        by_ip.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(&b.0)));
+        for (ip, &n) in by_ip.into_iter().filter(|&(_, &n)| n > 0) {
+            writeln!(
+                output,
+                "{:>4} {:<20} {:>10} {:>6.2}",
+                ip,
+                self.program.instructions[ip].to_string(),
+                n,
+                n as f64 * 100.0 / total
+            )?;
+        }
+
+        writeln!(output)?;
+        writeln!(output, "     block       count    executed       %")?;
+
+        let mut blocks = self.blocks();
+        blocks.sort_by_key(|b| (Reverse(b.executed()), b.start));
+        for b in blocks.into_iter().filter(|b| b.count > 0) {
+            writeln!(
+                output,
+                "{:>4} - {:<4} {:>10} {:>11} {:>6.2}",
+                b.start,
+                b.end,
+                b.count,
+                b.executed(),
+                b.executed() as f64 * 100.0 / total
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Block {
+    pub fn executed(&self) -> u64 {
+        self.count * (self.end - self.start + 1) as u64
+    }
+}
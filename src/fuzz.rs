@@ -0,0 +1,303 @@
+use crate::elfcode::{
+    optimise, run_on_device, Arithmetic, Budget, Compiled, Execution, Fault, Inst, Machine, Mode,
+    Opcode, Outcome, Program, Width, N_REGISTERS,
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    mem,
+};
+
+// random programs are small, and looping ones are cut short
+const MAX_LEN: u64 = 12;
+const BUDGET: Budget = Budget::Instructions(1000);
+
+// xorshift64*, so that the generated cases only depend on the seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // the state must never be 0
+        Rng((seed ^ 0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+}
+
+// a program and the state it starts from
+#[derive(Clone)]
+pub struct Case {
+    pub program: Program,
+    pub registers: [u64; N_REGISTERS],
+    pub mode: Mode,
+    // straight line programs using r0 to r3 and small values, which day 16 can run as well
+    pub straight: bool,
+}
+
+impl Display for Case {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "mode: {}", self.mode)?;
+        writeln!(f, "registers: {:?}", self.registers)?;
+        write!(f, "{}", self.program)
+    }
+}
+
+pub struct Divergence {
+    pub case_number: usize,
+    pub minimal: Case,
+    pub details: String,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "divergence in case {}", self.case_number)?;
+        writeln!(f, "{}", self.details)?;
+        writeln!(f, "minimal program:")?;
+        write!(f, "{}", self.minimal)
+    }
+}
+
+// generates random cases and runs them on every implementation:
+//  - the day 16 machine, for straight line programs
+//  - the elfcode interpreter, which is the reference
+//  - the compiled program, which must behave exactly the same
+//  - the optimised program, whose registers must end up the same
+// returns the first divergence, after reducing it to a minimal case
+pub fn run(seed: u64, n_cases: usize) -> Option<Divergence> {
+    let mut rng = Rng::new(seed);
+
+    for case_number in 0..n_cases {
+        let case = generate(&mut rng);
+        if check(&case).is_some() {
+            let minimal = minimise(case);
+            let details = check(&minimal).unwrap_or_default();
+            return Some(Divergence {
+                case_number,
+                minimal,
+                details,
+            });
+        }
+    }
+
+    None
+}
+
+fn generate(rng: &mut Rng) -> Case {
+    let straight = rng.one_in(2);
+
+    let (mode, ip_register, n_registers, max_imm) = if straight {
+        (Mode::default(), N_REGISTERS - 1, 4, u8::MAX as u64)
+    } else {
+        let arithmetic = match rng.below(3) {
+            0 => Arithmetic::Checked,
+            1 => Arithmetic::Wrapping,
+            _ => Arithmetic::Saturating,
+        };
+        let width = match rng.below(4) {
+            0 => Width::U8,
+            1 => Width::U16,
+            2 => Width::U32,
+            _ => Width::U64,
+        };
+        let mode = Mode { arithmetic, width };
+        (
+            mode,
+            rng.below(N_REGISTERS as u64) as usize,
+            N_REGISTERS,
+            u32::MAX as u64,
+        )
+    };
+
+    let len = 1 + rng.below(MAX_LEN);
+    let instructions = (0..len)
+        .map(|_| {
+            let opcode = Opcode::ALL[rng.below(16) as usize];
+            let (a_reg, b_reg) = opcode.operands();
+            let mut arg = |is_reg: bool| {
+                if !is_reg {
+                    value(rng, max_imm, len) as u32
+                } else if !straight && rng.one_in(100) {
+                    // invalid register
+                    (N_REGISTERS as u64 + rng.below(3)) as u32
+                } else {
+                    rng.below(n_registers as u64) as u32
+                }
+            };
+            Inst {
+                opcode,
+                args: [arg(a_reg), arg(b_reg), arg(true)],
+            }
+        })
+        .collect();
+
+    let mut registers = [0; N_REGISTERS];
+    for r in registers.iter_mut().take(n_registers) {
+        *r = value(rng, mode.width.max(), len);
+    }
+
+    Case {
+        program: Program {
+            ip_register,
+            instructions,
+        },
+        registers,
+        mode,
+        straight,
+    }
+}
+
+// mostly small values, to stay within the program, and values close to overflowing
+fn value(rng: &mut Rng, max: u64, len: u64) -> u64 {
+    match rng.below(4) {
+        0 => rng.below(4),
+        1 => rng.below(len + 2),
+        2 => max - rng.below(4).min(max),
+        _ => rng.next() & max,
+    }
+}
+
+// describes how the implementations disagree, if they do
+fn check(case: &Case) -> Option<String> {
+    let machine = || Machine {
+        registers: case.registers,
+        mode: case.mode,
+    };
+    let ip_register = case.program.ip_register;
+
+    let reference = case.program.execute(&mut machine(), BUDGET);
+    let compiled = Compiled::new(&case.program, case.mode).execute(&mut machine(), BUDGET);
+    let optimised = optimise(&case.program, case.mode).execute(&mut machine(), BUDGET);
+
+    if compiled != reference {
+        return Some(describe(&reference, "compiled", &compiled));
+    }
+
+    // the optimised program takes different paths, only the end result matters
+    let finished = |e: &Execution| e.outcome != Outcome::BudgetExhausted;
+    if finished(&reference) && finished(&optimised) {
+        let same_outcome =
+            mem::discriminant(&reference.outcome) == mem::discriminant(&optimised.outcome);
+        let same_registers = (0..N_REGISTERS)
+            .filter(|&r| r != ip_register)
+            .all(|r| reference.registers[r] == optimised.registers[r]);
+        if !same_outcome || !same_registers {
+            return Some(describe(&reference, "optimised", &optimised));
+        }
+    }
+
+    if case.straight {
+        let day16 = run_day16(case);
+        let same_registers = reference.registers[..4] == day16.registers[..4];
+        if reference.outcome != day16.outcome || reference.n_exec != day16.n_exec || !same_registers
+        {
+            return Some(describe(&reference, "day 16", &day16));
+        }
+    }
+
+    None
+}
+
+fn run_day16(case: &Case) -> Execution {
+    let mut registers = [0; 4];
+    registers.copy_from_slice(&case.registers[..4]);
+
+    let (result, fault) = run_on_device(&case.program.instructions, registers);
+    let (outcome, n_exec) = match fault {
+        None => (Outcome::Halted, case.program.instructions.len()),
+        Some((ip, Fault::Overflow)) => (Outcome::Overflow { ip }, ip),
+        Some((ip, Fault::InvalidRegister(register))) => {
+            (Outcome::InvalidRegister { ip, register }, ip)
+        }
+    };
+
+    let mut registers = case.registers;
+    registers[..4].copy_from_slice(&result);
+
+    Execution {
+        outcome,
+        n_exec: n_exec as u64,
+        registers,
+    }
+}
+
+fn describe(reference: &Execution, name: &str, other: &Execution) -> String {
+    format!(
+        "interpreter: {} after {} instructions, {:?}\n{}: {} after {} instructions, {:?}",
+        reference.outcome,
+        reference.n_exec,
+        reference.registers,
+        name,
+        other.outcome,
+        other.n_exec,
+        other.registers
+    )
+}
+
+// removes instructions and simplifies values for as long as the case still diverges
+fn minimise(mut case: Case) -> Case {
+    while let Some(smaller) = shrink(&case).into_iter().find(|c| check(c).is_some()) {
+        case = smaller;
+    }
+    case
+}
+
+// every case one step simpler than this one
+fn shrink(case: &Case) -> Vec<Case> {
+    let mut cases = vec![];
+
+    for i in 0..case.program.instructions.len() {
+        let mut c = case.clone();
+        c.program.instructions.remove(i);
+        cases.push(c);
+    }
+
+    for r in 0..N_REGISTERS {
+        let val = case.registers[r];
+        for &smaller in [0, val / 2].iter().filter(|&&v| v < val) {
+            let mut c = case.clone();
+            c.registers[r] = smaller;
+            cases.push(c);
+        }
+    }
+
+    for (i, inst) in case.program.instructions.iter().enumerate() {
+        let (a_reg, b_reg) = inst.opcode.operands();
+        for (arg, is_reg) in [(0, a_reg), (1, b_reg)].iter().cloned() {
+            let val = inst.args[arg];
+            if is_reg {
+                continue;
+            }
+            for &smaller in [0, val / 2].iter().filter(|&&v| v < val) {
+                let mut c = case.clone();
+                c.program.instructions[i].args[arg] = smaller;
+                cases.push(c);
+            }
+        }
+    }
+
+    cases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_divergence() {
+        if let Some(divergence) = run(2018, 2000) {
+            panic!("{}", divergence);
+        }
+    }
+}
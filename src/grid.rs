@@ -0,0 +1,220 @@
+use crate::point::Point;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter, Write},
+    io::{self, BufRead, BufReader},
+};
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+// a rectangle of cells stored row by row, x goes right and y goes down
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, val: T) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![val; width * height],
+        }
+    }
+
+    // same as from_reader, but rows shorter than the longest one are filled up with fill,
+    // for inputs whose trailing spaces may have been trimmed
+    // empty lines are rows too, except at the end
+    pub fn from_reader_padded<R, F>(r: R, f: F, fill: T) -> Result<Self, ParseError>
+    where
+        R: io::Read,
+        F: Fn(char) -> Option<T>,
+    {
+        let mut rows = read_rows(r, f)?;
+        while rows.last().is_some_and(|(_, row)| row.is_empty()) {
+            rows.pop();
+        }
+
+        let width = rows.iter().map(|(_, row)| row.len()).max().unwrap_or(0);
+        let height = rows.len();
+        let mut cells = Vec::with_capacity(width * height);
+        for (_, mut row) in rows {
+            row.resize(width, fill.clone());
+            cells.append(&mut row);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            cells,
+        })
+    }
+}
+
+impl<T> Grid<T> {
+    // one row per non empty line, all rows must have the same length
+    pub fn from_reader<R, F>(r: R, f: F) -> Result<Self, ParseError>
+    where
+        R: io::Read,
+        F: Fn(char) -> Option<T>,
+    {
+        let mut width = None;
+        let mut height = 0;
+        let mut cells = vec![];
+
+        for (line, mut row) in read_rows(r, f)? {
+            if row.is_empty() {
+                continue;
+            }
+
+            match width {
+                Some(w) if w != row.len() => {
+                    return Err(ParseError::new(
+                        line,
+                        format!("row of {} cells, expected {}", row.len(), w),
+                    ));
+                }
+                _ => width = Some(row.len()),
+            }
+            cells.append(&mut row);
+            height += 1;
+        }
+
+        Ok(Self {
+            width: width.unwrap_or(0),
+            height,
+            cells,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, p: Point) -> Option<usize> {
+        if p.x >= 0 && p.y >= 0 && (p.x as usize) < self.width && (p.y as usize) < self.height {
+            Some(p.y as usize * self.width + p.x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        self.index(p).is_some()
+    }
+
+    pub fn get(&self, p: Point) -> Option<&T> {
+        let i = self.index(p)?;
+        self.cells.get(i)
+    }
+
+    // returns false, leaving the grid untouched, if the point is outside
+    pub fn set(&mut self, p: Point, val: T) -> bool {
+        match self.index(p) {
+            Some(i) => {
+                self.cells[i] = val;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // all cells in reading order
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    // all points of the grid in reading order
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        let width = self.width as i64;
+        (0..(self.width * self.height) as i64).map(move |i| Point::new(i % width, i / width))
+    }
+
+    pub fn row(&self, y: usize) -> Option<&[T]> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y * self.width;
+        Some(&self.cells[start..start + self.width])
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks panics on 0
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn column(&self, x: usize) -> Option<impl Iterator<Item = &T>> {
+        if x >= self.width {
+            return None;
+        }
+        Some(self.cells[x..].iter().step_by(self.width))
+    }
+
+    // neighbours up, left, right and down that are inside the grid, in reading order
+    pub fn neighbours4(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        p.neighbours4().filter(move |&n| self.contains(n))
+    }
+
+    // same as neighbours4, with the diagonals
+    pub fn neighbours8(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        p.neighbours8().filter(move |&n| self.contains(n))
+    }
+}
+
+// the cells of every line, with its line number, empty lines included
+fn read_rows<T, R, F>(r: R, f: F) -> Result<Vec<(usize, Vec<T>)>, ParseError>
+where
+    R: io::Read,
+    F: Fn(char) -> Option<T>,
+{
+    let mut rows = vec![];
+    for (i, l) in BufReader::new(r).lines().enumerate() {
+        let l = l.map_err(|e| ParseError::new(i + 1, e.to_string()))?;
+        let row = l
+            .chars()
+            .map(|c| f(c).ok_or_else(|| ParseError::new(i + 1, format!("unknown cell: {}", c))))
+            .collect::<Result<_, _>>()?;
+        rows.push((i + 1, row));
+    }
+
+    Ok(rows)
+}
+
+impl<T: Copy + Into<char>> Display for Grid<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for row in self.rows() {
+            for &c in row {
+                f.write_char(c.into())?;
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,39 @@
+mod automaton;
+mod combat;
+mod cycle;
+mod elfcode;
+mod fuzz;
+mod grid;
+mod point;
+mod pots;
+mod route;
+mod search;
 mod solutions;
 mod solver;
+mod tools;
 
-use crate::solutions::exec_day;
-use std::env;
+use crate::{solutions::exec_day, tools::exec_tool};
+use std::{env, process};
 
 fn main() {
-    let day = env::args()
-        .nth(1)
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    // the first argument is either a tool name or a day number
+    if let Some(tool) = args.first() {
+        match exec_tool(tool, &args[1..]) {
+            Some(Ok(())) => return,
+            // so that scripts can tell when a check failed
+            Some(Err(e)) => {
+                eprintln!("{}: {}", tool, e);
+                process::exit(1);
+            }
+            None => {}
+        }
+    }
+
+    let day = args
+        .first()
+        .cloned()
         .unwrap_or_else(|| String::from("1"))
         .parse()
         .unwrap_or(1);
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Formatter},
+    ops::{Add, AddAssign, Sub},
+};
+
+// offsets of the neighbours, in reading order
+const NEIGHBOURS_4: [(i64, i64); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+const NEIGHBOURS_8: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+// x goes right and y goes down, like on the puzzle maps
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Point {
+    pub fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+
+    pub fn manhattan(&self, other: &Self) -> i64 {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
+    }
+
+    pub fn chebyshev(&self, other: &Self) -> i64 {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
+
+    // up, left, right and down, in reading order
+    pub fn neighbours4(self) -> impl Iterator<Item = Point> {
+        NEIGHBOURS_4
+            .iter()
+            .map(move |&(dx, dy)| self + Point::new(dx, dy))
+    }
+
+    // same as neighbours4, with the diagonals
+    pub fn neighbours8(self) -> impl Iterator<Item = Point> {
+        NEIGHBOURS_8
+            .iter()
+            .map(move |&(dx, dy)| self + Point::new(dx, dy))
+    }
+}
+
+// reading order: top to bottom, then left to right
+impl Ord for Point {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
+impl PartialOrd for Point {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Point {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl AddAssign for Point {
+    fn add_assign(&mut self, other: Point) {
+        *self = *self + other;
+    }
+}
+
+impl Add<Direction> for Point {
+    type Output = Point;
+
+    fn add(self, dir: Direction) -> Point {
+        self + dir.offset()
+    }
+}
+
+impl AddAssign<Direction> for Point {
+    fn add_assign(&mut self, dir: Direction) {
+        *self = *self + dir;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    pub fn offset(self) -> Point {
+        match self {
+            Direction::Up => Point::new(0, -1),
+            Direction::Right => Point::new(1, 0),
+            Direction::Down => Point::new(0, 1),
+            Direction::Left => Point::new(-1, 0),
+        }
+    }
+
+    pub fn turn_left(self) -> Self {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Right => Direction::Up,
+            Direction::Down => Direction::Right,
+            Direction::Left => Direction::Down,
+        }
+    }
+
+    pub fn turn_right(self) -> Self {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    pub fn reverse(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Right => Direction::Left,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+        }
+    }
+
+    pub fn is_horizontal(self) -> bool {
+        self == Direction::Left || self == Direction::Right
+    }
+}
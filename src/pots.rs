@@ -0,0 +1,178 @@
+use crate::cycle::simulate_drifting;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+
+// a row of pots, and the rules telling which ones get a plant at the next generation
+#[derive(Debug)]
+pub struct Garden {
+    state: Pots,
+    rules: Rules,
+}
+
+#[derive(Debug)]
+struct Rules {
+    // pots on each side of the one being updated
+    radius: usize,
+    // whether the middle pot gets a plant, by neighbourhood index
+    // the leftmost pot of a neighbourhood is the highest bit of its index
+    table: Vec<bool>,
+}
+
+impl Garden {
+    // reads the puzzle input, the initial state followed by the rules
+    pub fn from_reader<R: io::Read + io::Seek>(r: R) -> Self {
+        let mut r = BufReader::new(r);
+
+        r.seek(SeekFrom::Current(15)).expect("unable to seek");
+
+        let mut state_str = String::new();
+        r.read_line(&mut state_str)
+            .expect("unable to read state line");
+
+        let mut empty_line = String::new();
+        r.read_line(&mut empty_line)
+            .expect("unable to read empty line");
+
+        let rules = r
+            .lines()
+            .map_while(Result::ok)
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                let mut parts = s.split(" => ");
+                let from = parts.next().expect("no rule pattern").to_string();
+                let to = parts.next().expect("no rule result") == "#";
+
+                (from, to)
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            state: Pots::collect(0, state_str.trim_end().bytes().map(|b| b == b'#')),
+            rules: Rules::new(&rules).expect("invalid rules"),
+        }
+    }
+
+    // the sum of the indices of the pots with a plant after the given number of generations
+    // the plants end up moving as a block, the generations after that are skipped
+    pub fn plants_sum_after(&self, generations: usize) -> i64 {
+        let state = simulate_drifting(
+            self.state.clone(),
+            generations,
+            |s| *s = s.next(&self.rules),
+            Pots::shape,
+            Pots::shifted,
+        );
+
+        state.plants_sum()
+    }
+}
+
+impl Rules {
+    // all patterns must have the same odd width, missing ones leave the pot empty
+    fn new(rules: &[(String, bool)]) -> Result<Self, String> {
+        let width = rules.first().map_or(1, |(from, _)| from.len());
+        if width % 2 != 1 {
+            return Err(format!("rules must have an odd width, not {}", width));
+        }
+
+        let mut table = vec![false; 1 << width];
+        for (from, to) in rules {
+            if from.len() != width {
+                return Err(format!(
+                    "rule {} is {} pots wide, expected {}",
+                    from,
+                    from.len(),
+                    width
+                ));
+            }
+            let index = from
+                .bytes()
+                .fold(0, |i, b| (i << 1) | usize::from(b == b'#'));
+            table[index] = *to;
+        }
+
+        // otherwise the pots would fill up endlessly on both sides
+        if table[0] {
+            return Err(String::from("empty pots can't grow a plant"));
+        }
+
+        Ok(Self {
+            radius: width / 2,
+            table,
+        })
+    }
+}
+
+// a row of pots, stored one bit per pot from the first plant to the last one
+#[derive(Clone, Debug)]
+struct Pots {
+    bits: Vec<u64>,
+    len: usize,
+    // index of the first plant
+    first: i64,
+}
+
+impl Pots {
+    // pots starting at the given index, the empty ones on the sides are dropped
+    fn collect(index: i64, pots: impl IntoIterator<Item = bool>) -> Self {
+        let mut bits = vec![];
+        let mut first = None;
+        let mut len = 0;
+
+        for (i, plant) in pots.into_iter().enumerate() {
+            if !plant {
+                continue;
+            }
+            let j = i - *first.get_or_insert(i);
+            if bits.len() <= j / 64 {
+                bits.resize(j / 64 + 1, 0);
+            }
+            bits[j / 64] |= 1 << (j % 64);
+            len = j + 1;
+        }
+
+        Self {
+            bits,
+            len,
+            first: index + first.unwrap_or(0) as i64,
+        }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        i < self.len && (self.bits[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    fn next(&self, rules: &Rules) -> Self {
+        let radius = rules.radius;
+        let mask = rules.table.len() - 1;
+
+        // the neighbourhood of a pot slides along the row one bit at a time,
+        // pots up to radius away from a plant can get one
+        let mut window = 0;
+        Self::collect(
+            self.first - radius as i64,
+            (0..self.len + 2 * radius).map(|i| {
+                window = ((window << 1) | usize::from(self.get(i))) & mask;
+                rules.table[window]
+            }),
+        )
+    }
+
+    // the plants relative to the first one, and the index of the first one
+    fn shape(&self) -> ((Vec<u64>, usize), i64) {
+        ((self.bits.clone(), self.len), self.first)
+    }
+
+    fn shifted(self, offset: i64) -> Self {
+        Self {
+            first: self.first + offset,
+            ..self
+        }
+    }
+
+    fn plants_sum(&self) -> i64 {
+        (0..self.len)
+            .filter(|&i| self.get(i))
+            .map(|i| self.first + i as i64)
+            .sum()
+    }
+}
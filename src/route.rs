@@ -0,0 +1,388 @@
+use crate::{
+    grid::Grid,
+    point::{Direction, Point},
+    search::{bfs, Search},
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter, Write},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Dir {
+    N,
+    S,
+    E,
+    W,
+}
+
+impl Dir {
+    fn direction(&self) -> Direction {
+        match self {
+            Dir::N => Direction::Up,
+            Dir::S => Direction::Down,
+            Dir::E => Direction::Right,
+            Dir::W => Direction::Left,
+        }
+    }
+}
+
+impl Display for Dir {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let c = match self {
+            Dir::N => 'N',
+            Dir::S => 'S',
+            Dir::E => 'E',
+            Dir::W => 'W',
+        };
+        f.write_char(c)
+    }
+}
+
+// a route is a sequence of doors to go through, and of branches where any of several
+// routes can be taken, possibly an empty one
+#[derive(Debug, PartialEq, Eq)]
+pub struct Route(Vec<Step>);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Step {
+    Door(Dir),
+    Branch(Vec<Route>),
+}
+
+#[derive(Debug)]
+pub struct RegexError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl RegexError {
+    fn new(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for RegexError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl Error for RegexError {}
+
+// parses a whole ^...$ regex, trailing whitespace is ignored
+pub fn parse_route(s: &str) -> Result<Route, RegexError> {
+    let mut parser = RouteParser {
+        s: s.trim_end(),
+        pos: 0,
+    };
+
+    if parser.peek() != Some('^') {
+        return Err(RegexError::new(0, "missing ^"));
+    }
+    parser.pos += 1;
+
+    let route = parser.route()?;
+
+    match parser.peek() {
+        Some('$') => parser.pos += 1,
+        Some(')') => return Err(RegexError::new(parser.pos, "unbalanced )")),
+        Some('|') => return Err(RegexError::new(parser.pos, "| outside of a branch")),
+        _ => return Err(RegexError::new(parser.pos, "missing $")),
+    }
+    if let Some(c) = parser.peek() {
+        return Err(RegexError::new(
+            parser.pos,
+            format!("unexpected character after $: {}", c),
+        ));
+    }
+
+    Ok(route)
+}
+
+struct RouteParser<'a> {
+    s: &'a str,
+    // byte offset of the next character
+    pos: usize,
+}
+
+impl RouteParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    // stops before the first character that doesn't belong to a route
+    fn route(&mut self) -> Result<Route, RegexError> {
+        let mut steps = vec![];
+
+        while let Some(c) = self.peek() {
+            let dir = match c {
+                'N' => Dir::N,
+                'S' => Dir::S,
+                'E' => Dir::E,
+                'W' => Dir::W,
+                '(' => {
+                    steps.push(self.branch()?);
+                    continue;
+                }
+                '|' | ')' | '$' => break,
+                c => {
+                    return Err(RegexError::new(
+                        self.pos,
+                        format!("unknown character: {}", c),
+                    ))
+                }
+            };
+            steps.push(Step::Door(dir));
+            self.pos += 1;
+        }
+
+        Ok(Route(steps))
+    }
+
+    fn branch(&mut self) -> Result<Step, RegexError> {
+        let open = self.pos;
+        self.pos += 1;
+
+        let mut routes = vec![];
+        loop {
+            routes.push(self.route()?);
+            match self.peek() {
+                Some('|') => self.pos += 1,
+                Some(')') => {
+                    self.pos += 1;
+                    return Ok(Step::Branch(routes));
+                }
+                _ => return Err(RegexError::new(open, "unbalanced (")),
+            }
+        }
+    }
+}
+
+// the route as written in a regex, without ^ and $
+impl Display for Route {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for step in &self.0 {
+            match step {
+                Step::Door(d) => write!(f, "{}", d)?,
+                Step::Branch(routes) => {
+                    f.write_char('(')?;
+                    for (i, r) in routes.iter().enumerate() {
+                        if i > 0 {
+                            f.write_char('|')?;
+                        }
+                        write!(f, "{}", r)?;
+                    }
+                    f.write_char(')')?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Route {
+    pub fn to_regex(&self) -> String {
+        format!("^{}$", self)
+    }
+}
+
+// every room that was reached, with the directions of its doors
+// the start room is at 0,0
+pub struct Facility {
+    rooms: HashMap<Point, Vec<Direction>>,
+}
+
+impl Facility {
+    pub fn from_route(route: &Route) -> Self {
+        let start = Point::default();
+        let mut facility = Self {
+            rooms: HashMap::new(),
+        };
+        facility.rooms.insert(start, vec![]);
+        facility.walk(route, vec![start]);
+
+        facility
+    }
+
+    // follows the route from each of the given rooms, and returns every room where it can end,
+    // an empty route in a branch ends where it started
+    fn walk(&mut self, route: &Route, from: Vec<Point>) -> Vec<Point> {
+        route.0.iter().fold(from, |rooms, step| match step {
+            Step::Door(d) => rooms
+                .into_iter()
+                .map(|p| self.open_door(p, d.direction()))
+                .collect(),
+            Step::Branch(routes) => {
+                let mut ends = routes
+                    .iter()
+                    .flat_map(|r| self.walk(r, rooms.clone()))
+                    .collect::<Vec<_>>();
+                ends.sort();
+                ends.dedup();
+                ends
+            }
+        })
+    }
+
+    // returns the room on the other side
+    fn open_door(&mut self, room: Point, dir: Direction) -> Point {
+        let next = room + dir;
+        for &(p, d) in &[(room, dir), (next, dir.reverse())] {
+            let doors = self.rooms.entry(p).or_default();
+            if !doors.contains(&d) {
+                doors.push(d);
+            }
+        }
+
+        next
+    }
+
+    fn neighbours(&self, room: Point) -> impl Iterator<Item = Point> + '_ {
+        self.rooms
+            .get(&room)
+            .into_iter()
+            .flatten()
+            .map(move |&d| room + d)
+    }
+
+    // the number of doors to go through to reach the furthest room
+    pub fn furthest_room(&self) -> usize {
+        self.door_distances()
+            .costs()
+            .map(|(_, c)| c)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // how many rooms need at least the given number of doors to be reached from the start
+    pub fn rooms_at_least(&self, doors: usize) -> usize {
+        self.door_distances()
+            .costs()
+            .filter(|&(_, c)| c >= doors)
+            .count()
+    }
+
+    // the least number of doors to go through to reach each room from the start
+    fn door_distances(&self) -> Search<Point, usize> {
+        bfs(Point::default(), |p| self.neighbours(p), |&p| p)
+    }
+}
+
+// the map as drawn in the puzzle, rooms and doors are on odd and even cells of a grid
+// surrounded by walls, X is the start room
+impl Display for Facility {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let min_x = self.rooms.keys().map(|p| p.x).min().unwrap_or(0);
+        let min_y = self.rooms.keys().map(|p| p.y).min().unwrap_or(0);
+        let max_x = self.rooms.keys().map(|p| p.x).max().unwrap_or(0);
+        let max_y = self.rooms.keys().map(|p| p.y).max().unwrap_or(0);
+
+        let width = (max_x - min_x + 1) as usize;
+        let height = (max_y - min_y + 1) as usize;
+        let mut map = Grid::new(2 * width + 1, 2 * height + 1, '#');
+
+        for (&room, doors) in &self.rooms {
+            let p = Point::new(2 * (room.x - min_x) + 1, 2 * (room.y - min_y) + 1);
+            map.set(p, if room == Point::default() { 'X' } else { '.' });
+            for &d in doors {
+                map.set(p + d, if d.is_horizontal() { '|' } else { '-' });
+            }
+        }
+
+        write!(f, "{}", map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facility(regex: &str) -> Facility {
+        Facility::from_route(&parse_route(regex).unwrap())
+    }
+
+    #[test]
+    fn rooms_at_least() {
+        // the rooms of this example are at most 18 doors away
+        let f = facility("^ENNWSWW(NEWS|)SSSEEN(WNSE|)EE(SWEN|)NNN$");
+        let distances = f.door_distances();
+        let room_count = distances.costs().count();
+
+        assert_eq!(f.rooms_at_least(0), room_count);
+        assert_eq!(f.rooms_at_least(1), room_count - 1);
+        assert_eq!(f.rooms_at_least(18), 1);
+        assert_eq!(f.rooms_at_least(19), 0);
+
+        // a corridor with one room at each distance, and two at its end
+        let f = facility("^EEEE(N|S)$");
+        assert_eq!(f.rooms_at_least(3), 4);
+        assert_eq!(f.rooms_at_least(5), 2);
+        assert_eq!(f.rooms_at_least(6), 0);
+    }
+
+    #[test]
+    fn map() {
+        let f = facility("^ENWWW(NEEE|SSE(EE|N))$");
+        let expected = "\
+#########
+#.|.|.|.#
+#-#######
+#.|.|.|.#
+#-#####-#
+#.#.#X|.#
+#-#-#####
+#.|.|.|.#
+#########
+";
+        assert_eq!(f.to_string(), expected);
+    }
+
+    // the puzzle examples, with the number of doors to the furthest room
+    const EXAMPLES: [(&str, usize); 5] = [
+        ("^WNE$", 3),
+        ("^ENWWW(NEEE|SSE(EE|N))$", 10),
+        ("^ENNWSWW(NEWS|)SSSEEN(WNSE|)EE(SWEN|)NNN$", 18),
+        ("^ESSWWN(E|NNENN(EESS(WNSE|)SSS|WWWSSSSE(SW|NNNE)))$", 23),
+        (
+            "^WSSEESWWWNW(S|NENNEEEENN(ESSSSW(NWSW|SSEN)|WSWWN(E|WWS(E|SS))))$",
+            31,
+        ),
+    ];
+
+    #[test]
+    fn round_trip() {
+        for &(regex, furthest) in EXAMPLES.iter() {
+            let route = parse_route(regex).unwrap();
+            assert_eq!(route.to_regex(), regex);
+            assert_eq!(parse_route(&route.to_regex()).unwrap(), route);
+            assert_eq!(Facility::from_route(&route).furthest_room(), furthest);
+        }
+    }
+
+    fn error(regex: &str) -> (usize, String) {
+        let e = parse_route(regex).unwrap_err();
+        (e.offset, e.message)
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("N$"), (0, String::from("missing ^")));
+        assert_eq!(error("^NE"), (3, String::from("missing $")));
+        assert_eq!(error("^N(E|W$"), (2, String::from("unbalanced (")));
+        assert_eq!(error("^N(E|W))$"), (7, String::from("unbalanced )")));
+        assert_eq!(error("^N|E$"), (2, String::from("| outside of a branch")));
+        assert_eq!(
+            error("^N(EX|W)$"),
+            (4, String::from("unknown character: X"))
+        );
+        assert_eq!(
+            error("^NE$W"),
+            (4, String::from("unexpected character after $: W"))
+        );
+    }
+}
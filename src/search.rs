@@ -0,0 +1,218 @@
+use crate::{grid::Grid, point::Point};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    hash::Hash,
+    ops::Add,
+};
+
+// costs and shortest paths from one start node to every node that was reached
+pub struct Search<N, C> {
+    costs: HashMap<N, C>,
+    parents: HashMap<N, N>,
+}
+
+impl<N: Copy + Eq + Hash, C: Copy> Search<N, C> {
+    pub fn cost(&self, n: N) -> Option<C> {
+        self.costs.get(&n).cloned()
+    }
+
+    pub fn costs(&self) -> impl Iterator<Item = (N, C)> + '_ {
+        self.costs.iter().map(|(&n, &c)| (n, c))
+    }
+
+    // nodes from the start to this one, both included
+    pub fn path(&self, to: N) -> Option<Vec<N>> {
+        if !self.costs.contains_key(&to) {
+            return None;
+        }
+
+        let mut path = vec![to];
+        let mut n = to;
+        while let Some(&p) = self.parents.get(&n) {
+            path.push(p);
+            n = p;
+        }
+        path.reverse();
+
+        Some(path)
+    }
+}
+
+// every edge costs 1
+// the neighbours of a node are visited by increasing key, so that the path kept for each node
+// is the smallest in that order among its shortest paths, eg. reading order on a map
+pub fn bfs<N, I, K>(
+    start: N,
+    mut neighbours: impl FnMut(N) -> I,
+    key: impl Fn(&N) -> K,
+) -> Search<N, usize>
+where
+    N: Copy + Eq + Hash,
+    I: IntoIterator<Item = N>,
+    K: Ord,
+{
+    let mut costs = HashMap::new();
+    let mut parents = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    costs.insert(start, 0);
+    queue.push_back(start);
+
+    while let Some(n) = queue.pop_front() {
+        let cost = costs[&n] + 1;
+
+        let mut next = neighbours(n).into_iter().collect::<Vec<_>>();
+        next.sort_by_key(|m| key(m));
+
+        for m in next {
+            if costs.contains_key(&m) {
+                continue;
+            }
+            costs.insert(m, cost);
+            parents.insert(m, n);
+            queue.push_back(m);
+        }
+    }
+
+    Search { costs, parents }
+}
+
+// same as bfs, on the cells of a grid for which open is true, moving up, left, right or down
+// returns the distance to every cell that was reached, along with the first step to take
+// to get there, the first in reading order among those of the shortest paths
+// the start cell doesn't need to be open
+pub fn distance_field<T>(
+    grid: &Grid<T>,
+    start: Point,
+    open: impl Fn(&T) -> bool,
+) -> Grid<Option<(usize, Point)>> {
+    let mut field = Grid::new(grid.width(), grid.height(), None);
+    let mut queue = VecDeque::new();
+    let open_neighbours = |p| {
+        grid.neighbours4(p)
+            .filter(|&n| grid.get(n).is_some_and(&open))
+            .collect::<Vec<_>>()
+    };
+
+    field.set(start, Some((0, start)));
+    for n in open_neighbours(start) {
+        field.set(n, Some((1, n)));
+        queue.push_back(n);
+    }
+
+    while let Some(p) = queue.pop_front() {
+        let (dist, first) = match field.get(p) {
+            Some(&Some(f)) => f,
+            _ => continue,
+        };
+
+        // a whole distance is done before the next one starts, so a cell gets
+        // its best first step before being expanded itself
+        for n in open_neighbours(p) {
+            match field.get(n) {
+                Some(None) => {
+                    field.set(n, Some((dist + 1, first)));
+                    queue.push_back(n);
+                }
+                Some(&Some((d, f))) if d == dist + 1 && first < f => {
+                    field.set(n, Some((d, first)));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    field
+}
+
+// neighbours come with the cost of the edge leading to them
+// nodes with the same cost are expanded by increasing key, and a node keeps the first parent
+// that reached it with its final cost
+pub fn dijkstra<N, C, I, K>(
+    start: N,
+    neighbours: impl FnMut(N) -> I,
+    key: impl Fn(&N) -> K,
+) -> Search<N, C>
+where
+    N: Copy + Eq + Hash,
+    C: Copy + Ord + Add<Output = C> + Default,
+    I: IntoIterator<Item = (N, C)>,
+    K: Ord,
+{
+    best_first(start, neighbours, |_| C::default(), |_| false, key).0
+}
+
+// same as dijkstra, stops at the first goal node and returns its cost and path
+// the heuristic must never overestimate the cost to the nearest goal
+pub fn astar<N, C, I, K>(
+    start: N,
+    is_goal: impl Fn(&N) -> bool,
+    neighbours: impl FnMut(N) -> I,
+    heuristic: impl Fn(&N) -> C,
+    key: impl Fn(&N) -> K,
+) -> Option<(C, Vec<N>)>
+where
+    N: Copy + Eq + Hash,
+    C: Copy + Ord + Add<Output = C> + Default,
+    I: IntoIterator<Item = (N, C)>,
+    K: Ord,
+{
+    let (search, goal) = best_first(start, neighbours, heuristic, is_goal, key);
+    let goal = goal?;
+    Some((search.cost(goal)?, search.path(goal)?))
+}
+
+fn best_first<N, C, I, K>(
+    start: N,
+    mut neighbours: impl FnMut(N) -> I,
+    heuristic: impl Fn(&N) -> C,
+    is_goal: impl Fn(&N) -> bool,
+    key: impl Fn(&N) -> K,
+) -> (Search<N, C>, Option<N>)
+where
+    N: Copy + Eq + Hash,
+    C: Copy + Ord + Add<Output = C> + Default,
+    I: IntoIterator<Item = (N, C)>,
+    K: Ord,
+{
+    let mut costs = HashMap::new();
+    let mut parents = HashMap::new();
+    let mut done = HashSet::new();
+
+    // the heap refers to nodes by index, so that they don't need to be ordered themselves
+    let mut nodes = vec![start];
+    let mut heap = BinaryHeap::new();
+
+    costs.insert(start, C::default());
+    heap.push(Reverse((heuristic(&start), key(&start), 0)));
+
+    while let Some(Reverse((_, _, i))) = heap.pop() {
+        let n = nodes[i];
+        if !done.insert(n) {
+            continue;
+        }
+        if is_goal(&n) {
+            return (Search { costs, parents }, Some(n));
+        }
+
+        let cost = costs[&n];
+        for (m, c) in neighbours(n) {
+            let new_cost = cost + c;
+            if done.contains(&m) || costs.get(&m).is_some_and(|&old| old <= new_cost) {
+                continue;
+            }
+            costs.insert(m, new_cost);
+            parents.insert(m, n);
+
+            nodes.push(m);
+            heap.push(Reverse((
+                new_cost + heuristic(&m),
+                key(&m),
+                nodes.len() - 1,
+            )));
+        }
+    }
+
+    (Search { costs, parents }, None)
+}
@@ -1,4 +1,4 @@
-use crate::solver::Solver;
+use crate::{point::Point, solver::Solver};
 use std::{
     collections::{HashMap, HashSet},
     io::{self, BufRead, BufReader},
@@ -7,11 +7,11 @@ use std::{
 pub struct Problem;
 
 impl Solver for Problem {
-    type Input = Vec<Pt>;
+    type Input = Vec<Point>;
     type Output1 = u64;
     type Output2 = u64;
 
-    fn parse_input<R: io::Read>(&self, r: R) -> Vec<Pt> {
+    fn parse_input<R: io::Read>(&self, r: R) -> Vec<Point> {
         let r = BufReader::new(r);
         r.lines()
             .flatten()
@@ -19,12 +19,12 @@ impl Solver for Problem {
                 let mut s = line.split(", ");
                 let x = s.next()?.parse().ok()?;
                 let y = s.next()?.parse().ok()?;
-                Some(Pt { x, y })
+                Some(Point::new(x, y))
             })
             .collect()
     }
 
-    fn solve_first(&self, input: &Vec<Pt>) -> u64 {
+    fn solve_first(&self, input: &Vec<Point>) -> u64 {
         let min_x = input.iter().min_by_key(|e| e.x).unwrap().x;
         let min_y = input.iter().min_by_key(|e| e.y).unwrap().y;
         let max_x = input.iter().max_by_key(|e| e.x).unwrap().x;
@@ -35,11 +35,11 @@ impl Solver for Problem {
 
         for x in min_x..=max_x {
             for y in min_y..=max_y {
-                let current_point = Pt { x, y };
+                let current_point = Point::new(x, y);
                 let dists = input
                     .iter()
                     .enumerate()
-                    .map(|(i, pt)| (i, pt.dist(&current_point)))
+                    .map(|(i, pt)| (i, pt.manhattan(&current_point)))
                     .collect::<Vec<_>>();
                 let &(id, min_dist) = dists.iter().min_by_key(|&&(_, s)| s).unwrap();
                 let n_min_dist = dists.iter().filter(|&&(_, s)| s == min_dist).count();
@@ -65,7 +65,7 @@ impl Solver for Problem {
         *area as u64
     }
 
-    fn solve_second(&self, input: &Vec<Pt>) -> u64 {
+    fn solve_second(&self, input: &Vec<Point>) -> u64 {
         let min_x = input.iter().min_by_key(|e| e.x).unwrap().x;
         let min_y = input.iter().min_by_key(|e| e.y).unwrap().y;
         let max_x = input.iter().max_by_key(|e| e.x).unwrap().x;
@@ -75,8 +75,8 @@ impl Solver for Problem {
 
         for x in min_x..=max_x {
             for y in min_y..=max_y {
-                let current_point = Pt { x, y };
-                let total_dist: i32 = input.iter().map(|pt| pt.dist(&current_point)).sum();
+                let current_point = Point::new(x, y);
+                let total_dist: i64 = input.iter().map(|pt| pt.manhattan(&current_point)).sum();
 
                 if total_dist < 10000 {
                     areas.insert(current_point, total_dist);
@@ -87,21 +87,3 @@ impl Solver for Problem {
         areas.len() as u64
     }
 }
-
-#[derive(Debug, Eq, Hash)]
-pub struct Pt {
-    x: i32,
-    y: i32,
-}
-
-impl PartialEq for Pt {
-    fn eq(&self, other: &Self) -> bool {
-        self.x == other.x && self.y == other.y
-    }
-}
-
-impl Pt {
-    fn dist(&self, other: &Self) -> i32 {
-        (self.x - other.x).abs() + (self.y - other.y).abs()
-    }
-}
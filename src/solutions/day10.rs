@@ -1,4 +1,4 @@
-use crate::solver::Solver;
+use crate::{point::Point, solver::Solver};
 use regex::Regex;
 use std::io::{self, BufRead, BufReader};
 
@@ -19,14 +19,14 @@ impl Solver for Problem {
             .filter_map(|s| {
                 re.captures(s.as_str()).and_then(|c| {
                     Some(PointEntry {
-                        pos: Coords {
-                            x: c.get(1)?.as_str().parse().ok()?,
-                            y: c.get(2)?.as_str().parse().ok()?,
-                        },
-                        velocity: Coords {
-                            x: c.get(3)?.as_str().parse().ok()?,
-                            y: c.get(4)?.as_str().parse().ok()?,
-                        },
+                        pos: Point::new(
+                            c.get(1)?.as_str().parse().ok()?,
+                            c.get(2)?.as_str().parse().ok()?,
+                        ),
+                        velocity: Point::new(
+                            c.get(3)?.as_str().parse().ok()?,
+                            c.get(4)?.as_str().parse().ok()?,
+                        ),
                     })
                 })
             })
@@ -74,28 +74,16 @@ impl Solver for Problem {
 
 #[derive(Debug)]
 pub struct PointEntry {
-    pos: Coords,
-    velocity: Coords,
-}
-
-#[derive(Debug)]
-pub struct Coords {
-    x: i32,
-    y: i32,
+    pos: Point,
+    velocity: Point,
 }
 
 fn next_state(points: &[PointEntry]) -> Vec<PointEntry> {
     points
         .iter()
         .map(|e| PointEntry {
-            pos: Coords {
-                x: e.pos.x + e.velocity.x,
-                y: e.pos.y + e.velocity.y,
-            },
-            velocity: Coords {
-                x: e.velocity.x,
-                y: e.velocity.y,
-            },
+            pos: e.pos + e.velocity,
+            velocity: e.velocity,
         })
         .collect()
 }
@@ -123,7 +111,7 @@ fn print_grid(points: &[PointEntry]) {
     let mut out = String::new();
     for y in min_y..=max_y {
         for x in min_x..=max_x {
-            let c = if points.iter().any(|p| p.pos.x == x && p.pos.y == y) {
+            let c = if points.iter().any(|p| p.pos == Point::new(x, y)) {
                 '◻'
             } else {
                 ' '
@@ -1,20 +1,20 @@
-use crate::solver::Solver;
+use crate::{point::Point, solver::Solver};
 use std::{cmp::min, io};
 
 pub struct Problem;
 
 impl Solver for Problem {
-    type Input = i32;
+    type Input = i64;
     type Output1 = String;
     type Output2 = String;
 
-    fn parse_input<R: io::Read>(&self, mut r: R) -> i32 {
+    fn parse_input<R: io::Read>(&self, mut r: R) -> i64 {
         let mut s = String::new();
         r.read_to_string(&mut s).expect("unable to read to string");
         s.parse().unwrap()
     }
 
-    fn solve_first(&self, input: &i32) -> String {
+    fn solve_first(&self, input: &i64) -> String {
         let powers = compute_all_powers(*input);
         let mut max_square: Option<SquarePower> = None;
 
@@ -23,13 +23,13 @@ impl Solver for Problem {
                 let mut sum = 0;
                 for j in 0..3 {
                     for i in 0..3 {
-                        let pt = Pt { x: x + i, y: y + j };
+                        let pt = Point::new(x + i, y + j);
                         let power = get_power(&powers, &pt);
                         sum += power;
                     }
                 }
                 let current_square = SquarePower {
-                    pt: Pt { x, y },
+                    pt: Point::new(x, y),
                     size: 3,
                     power: sum,
                 };
@@ -47,7 +47,7 @@ impl Solver for Problem {
         )
     }
 
-    fn solve_second(&self, input: &i32) -> String {
+    fn solve_second(&self, input: &i64) -> String {
         let powers = compute_all_powers(*input);
         let mut max_square: Option<SquarePower> = None;
 
@@ -60,24 +60,18 @@ impl Solver for Problem {
                 let mut current_sum = 0;
                 for n in 1..=size {
                     for i in 0..n {
-                        let pt = Pt {
-                            x: x + i,
-                            y: y + (n - 1),
-                        };
+                        let pt = Point::new(x + i, y + (n - 1));
                         let power = get_power(&powers, &pt);
                         current_sum += power;
                     }
                     for i in 0..n - 1 {
-                        let pt = Pt {
-                            x: x + (n - 1),
-                            y: y + i,
-                        };
+                        let pt = Point::new(x + (n - 1), y + i);
                         let power = get_power(&powers, &pt);
                         current_sum += power;
                     }
 
                     let current_square = SquarePower {
-                        pt: Pt { x, y },
+                        pt: Point::new(x, y),
                         size: n as usize,
                         power: current_sum,
                     };
@@ -100,23 +94,17 @@ impl Solver for Problem {
     }
 }
 
-#[derive(Debug, Hash, Eq, PartialEq)]
-pub struct Pt {
-    x: i32,
-    y: i32,
-}
-
-fn power(pt: &Pt, serial_number: i32) -> i32 {
+fn power(pt: &Point, serial_number: i64) -> i64 {
     let rack_id = pt.x + 10;
     let p = (rack_id * pt.y + serial_number) * rack_id;
     (p / 100) % 10 - 5
 }
 
-fn compute_all_powers(serial_number: i32) -> Vec<i32> {
+fn compute_all_powers(serial_number: i64) -> Vec<i64> {
     let mut sol = Vec::with_capacity(300 * 300);
     for y in 1..=300 {
         for x in 1..=300 {
-            let pt = Pt { x, y };
+            let pt = Point::new(x, y);
             let pow = power(&pt, serial_number);
             sol.push(pow);
         }
@@ -125,13 +113,13 @@ fn compute_all_powers(serial_number: i32) -> Vec<i32> {
 }
 
 #[inline]
-fn get_power(powers: &[i32], pt: &Pt) -> i32 {
+fn get_power(powers: &[i64], pt: &Point) -> i64 {
     let i = (pt.x - 1) + (pt.y - 1) * 300;
     *powers.get(i as usize).unwrap_or(&0)
 }
 
 struct SquarePower {
-    pt: Pt,
+    pt: Point,
     size: usize,
-    power: i32,
+    power: i64,
 }
@@ -1,8 +1,9 @@
-use crate::solver::Solver;
-use std::{
-    cmp::Ordering,
-    io::{self, BufRead, BufReader},
+use crate::{
+    grid::Grid,
+    point::{Direction, Point},
+    solver::Solver,
 };
+use std::io;
 
 pub struct Problem;
 
@@ -12,43 +13,33 @@ impl Solver for Problem {
     type Output2 = String;
 
     fn parse_input<R: io::Read>(&self, r: R) -> RailSystem {
-        let mut tracks: Vec<Vec<u8>> = BufReader::new(r)
-            .lines()
-            .filter_map(|l| l.ok())
-            .map(|s| s.into_bytes())
-            .collect();
+        // lines are as long as their last track, the rest is empty ground
+        let mut tracks = Grid::from_reader_padded(
+            r,
+            |c| match c {
+                ' ' | '-' | '|' | '/' | '\\' | '+' | '>' | 'v' | '<' | '^' => Some(c as u8),
+                _ => None,
+            },
+            b' ',
+        )
+        .expect("unable to parse tracks");
         let mut carts = vec![];
 
-        for (y, t) in tracks.iter_mut().enumerate() {
-            for (x, b) in t.iter_mut().enumerate() {
-                let dir = match b {
-                    b'>' => {
-                        *b = b'-';
-                        Some(Dir::Right)
-                    }
-                    b'v' => {
-                        *b = b'|';
-                        Some(Dir::Down)
-                    }
-                    b'<' => {
-                        *b = b'-';
-                        Some(Dir::Left)
-                    }
-                    b'^' => {
-                        *b = b'|';
-                        Some(Dir::Up)
-                    }
-                    _ => None,
-                };
-                if let Some(dir) = dir {
-                    carts.push(Cart {
-                        pos: Pos { x, y },
-                        dir,
-                        next_turn: Turn::Left,
-                        deleted: false,
-                    })
-                }
-            }
+        for pos in tracks.points() {
+            let (dir, track) = match tracks.get(pos) {
+                Some(b'>') => (Direction::Right, b'-'),
+                Some(b'v') => (Direction::Down, b'|'),
+                Some(b'<') => (Direction::Left, b'-'),
+                Some(b'^') => (Direction::Up, b'|'),
+                _ => continue,
+            };
+            tracks.set(pos, track);
+            carts.push(Cart {
+                pos,
+                dir,
+                next_turn: Turn::Left,
+                deleted: false,
+            })
         }
 
         RailSystem { tracks, carts }
@@ -59,7 +50,7 @@ impl Solver for Problem {
         loop {
             let collisions = sys.step();
             if !collisions.is_empty() {
-                return format!("{},{}", collisions[0].x, collisions[0].y);
+                return collisions[0].to_string();
             }
         }
     }
@@ -70,7 +61,7 @@ impl Solver for Problem {
             let _ = sys.step();
 
             if sys.carts.len() == 1 {
-                return format!("{},{}", sys.carts[0].pos.x, sys.carts[0].pos.y);
+                return sys.carts[0].pos.to_string();
             }
         }
     }
@@ -78,7 +69,7 @@ impl Solver for Problem {
 
 #[derive(Clone)]
 pub struct RailSystem {
-    tracks: Vec<Vec<u8>>,
+    tracks: Grid<u8>,
     carts: Vec<Cart>,
 }
 
@@ -88,30 +79,21 @@ impl RailSystem {
         let mut tracks = self.tracks.clone();
         for cart in self.carts.iter() {
             let c = match cart.dir {
-                Dir::Right => b'>',
-                Dir::Down => b'v',
-                Dir::Left => b'<',
-                Dir::Up => b'^',
+                Direction::Right => b'>',
+                Direction::Down => b'v',
+                Direction::Left => b'<',
+                Direction::Up => b'^',
             };
-            tracks[cart.pos.y][cart.pos.x] = c;
-        }
-        for track in tracks {
-            println!("{}", String::from_utf8(track).unwrap());
+            tracks.set(cart.pos, c);
         }
+        println!("{}", tracks);
     }
 
-    fn step(&mut self) -> Vec<Pos> {
+    fn step(&mut self) -> Vec<Point> {
         let mut carts = self.carts.clone();
 
         // sort carts by x,y position
-        carts.sort_by(|a, b| {
-            let o = a.pos.x.cmp(&b.pos.x);
-            if o == Ordering::Equal {
-                a.pos.y.cmp(&b.pos.y)
-            } else {
-                o
-            }
-        });
+        carts.sort_by_key(|c| (c.pos.x, c.pos.y));
 
         let mut deleted_pos = vec![];
 
@@ -122,37 +104,19 @@ impl RailSystem {
                 continue;
             }
 
-            let t = self.tracks[cart.pos.y][cart.pos.x];
+            let t = self.tracks.get(cart.pos);
             cart.dir = match t {
-                b'/' => cart.dir.mirror_normal(),
-                b'\\' => cart.dir.mirror_reverse(),
-                b'+' => {
-                    let d = cart.next_turn.dir(&cart.dir);
+                Some(b'/') => mirror_normal(cart.dir),
+                Some(b'\\') => mirror_reverse(cart.dir),
+                Some(b'+') => {
+                    let d = cart.next_turn.dir(cart.dir);
                     cart.next_turn = cart.next_turn.next();
                     d
                 }
                 _ => cart.dir,
             };
 
-            let mut new_pos = Pos {
-                x: cart.pos.x,
-                y: cart.pos.y,
-            };
-
-            match cart.dir {
-                Dir::Down => {
-                    new_pos.y += 1;
-                }
-                Dir::Up => {
-                    new_pos.y -= 1;
-                }
-                Dir::Right => {
-                    new_pos.x += 1;
-                }
-                Dir::Left => {
-                    new_pos.x -= 1;
-                }
-            }
+            let new_pos = cart.pos + cart.dir;
 
             // find out if we overwrite an existing point
             {
@@ -180,46 +144,28 @@ impl RailSystem {
 
 #[derive(Clone)]
 pub struct Cart {
-    pos: Pos,
-    dir: Dir,
+    pos: Point,
+    dir: Direction,
     next_turn: Turn,
     deleted: bool,
 }
 
-#[derive(Copy, Clone)]
-pub enum Dir {
-    Right,
-    Down,
-    Left,
-    Up,
-}
-
-impl Dir {
-    fn mirror_normal(&self) -> Self {
-        // handles the '/' turn
-        match self {
-            Dir::Right => Dir::Up,
-            Dir::Down => Dir::Left,
-            Dir::Left => Dir::Down,
-            Dir::Up => Dir::Right,
-        }
-    }
-
-    fn mirror_reverse(&self) -> Self {
-        // handles the '\' turn
-        match self {
-            Dir::Right => Dir::Down,
-            Dir::Down => Dir::Right,
-            Dir::Left => Dir::Up,
-            Dir::Up => Dir::Left,
-        }
+// handles the '/' turn
+fn mirror_normal(dir: Direction) -> Direction {
+    if dir.is_horizontal() {
+        dir.turn_left()
+    } else {
+        dir.turn_right()
     }
 }
 
-#[derive(Eq, PartialEq, Hash, Clone)]
-pub struct Pos {
-    x: usize,
-    y: usize,
+// handles the '\' turn
+fn mirror_reverse(dir: Direction) -> Direction {
+    if dir.is_horizontal() {
+        dir.turn_right()
+    } else {
+        dir.turn_left()
+    }
 }
 
 #[derive(Clone)]
@@ -238,17 +184,46 @@ impl Turn {
         }
     }
 
-    fn dir(&self, dir: &Dir) -> Dir {
-        match (self, dir) {
-            (Turn::Left, Dir::Left) => Dir::Down,
-            (Turn::Left, Dir::Right) => Dir::Up,
-            (Turn::Left, Dir::Up) => Dir::Left,
-            (Turn::Left, Dir::Down) => Dir::Right,
-            (Turn::Right, Dir::Left) => Dir::Up,
-            (Turn::Right, Dir::Right) => Dir::Down,
-            (Turn::Right, Dir::Up) => Dir::Right,
-            (Turn::Right, Dir::Down) => Dir::Left,
-            (Turn::Straight, &dir) => dir,
+    fn dir(&self, dir: Direction) -> Direction {
+        match self {
+            Turn::Left => dir.turn_left(),
+            Turn::Straight => dir,
+            Turn::Right => dir.turn_right(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // the puzzle examples without their trailing spaces
+    const CRASH: &str = r"/->-\
+|   |  /----\
+| /-+--+-\  |
+| | |  | v  |
+\-+-/  \-+--/
+  \------/
+";
+
+    const LAST_CART: &str = r"/>-<\
+|   |
+| /<+-\
+| | | v
+\>+</ |
+  |   ^
+  \<->/
+";
+
+    #[test]
+    fn ragged_tracks() {
+        let sys = Problem.parse_input(Cursor::new(CRASH));
+        assert_eq!(sys.tracks.width(), 13);
+        assert_eq!(sys.tracks.height(), 6);
+        assert_eq!(Problem.solve_first(&sys), "7,3");
+
+        let sys = Problem.parse_input(Cursor::new(LAST_CART));
+        assert_eq!(Problem.solve_second(&sys), "6,4");
+    }
+}
@@ -1,7 +1,12 @@
-use crate::solver::Solver;
+use crate::{
+    elfcode::{Device, DeviceInst, DEVICE_INSTS},
+    solver::Solver,
+};
 use regex::Regex;
 use std::{
     collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    hash::Hash,
     io::{self, BufRead, BufReader},
 };
 
@@ -102,309 +107,467 @@ impl Solver for Problem {
     }
 
     fn solve_second(&self, input: &Input) -> u64 {
-        let mappings = find_opcode_mapping(&input.tests);
-        println!("{:#?}", mappings);
-        println!("{:#?}", input.program);
-
-        let mut m = Machine::load((0, 0, 0, 0));
-        for opcode in input.program.iter() {
-            let instr = mappings.get(&opcode.0).unwrap();
-            m.exec((*instr, opcode.1, opcode.2, opcode.3));
-        }
-
-        m.registers[0]
-    }
-}
-
-fn find_opcode_mapping(tests: &[TestCase]) -> HashMap<u8, Inst> {
-    let mut possible_matches = vec![HashSet::<Inst>::new(); 16];
-
-    for tc in tests.iter() {
-        let results = test_all_for_input(tc.before, tc.opcode);
-        let matches = results
-            .iter()
-            .filter(|&&(_, r)| r == tc.after)
-            .map(|(i, _)| *i)
-            .collect::<HashSet<_>>();
-
-        //println!("{} => {:#?}", tc.opcode.0, matches);
-
-        let existing_matches = possible_matches[tc.opcode.0 as usize]
+        let samples = input
+            .tests
             .iter()
-            .cloned()
-            .collect::<HashSet<_>>();
-        if existing_matches.is_empty() {
-            possible_matches[tc.opcode.0 as usize] = matches;
-        } else {
-            let inter = matches
-                .intersection(&existing_matches)
-                .cloned()
-                .collect::<HashSet<_>>();
-            possible_matches[tc.opcode.0 as usize] = inter;
-        }
-    }
-
-    //println!("{:#?}", possible_matches);
-
-    // final map
-    let mut mappings: HashMap<u8, Inst> = HashMap::new();
-
-    // remove in ascending order
-    loop {
-        let (instr, opcode) = {
-            let (instr, opcodes) = possible_matches
-                .iter()
-                .enumerate()
-                .find(|(_, m)| m.len() == 1)
-                .expect("unsolvable");
-
-            (instr as u8, *opcodes.iter().next().unwrap())
+            .map(|tc| (tc.opcode.0, matching_instructions(tc)))
+            .collect::<Vec<_>>();
+
+        let mapping = match solve_mapping(&samples, &DEVICE_INSTS, MAX_MAPPINGS) {
+            Mapping::Unique(mapping) => mapping,
+            Mapping::Ambiguous {
+                mappings,
+                truncated,
+            } => {
+                println!(
+                    "ambiguous opcodes, {}{} consistent mappings",
+                    mappings.len(),
+                    if truncated { "+" } else { "" }
+                );
+                return 0;
+            }
+            Mapping::Contradiction(conflicts) => {
+                for c in conflicts.iter() {
+                    println!("{}", c);
+                }
+                return 0;
+            }
         };
 
-        mappings.insert(instr, opcode);
-
-        for m in possible_matches.iter_mut() {
-            m.remove(&opcode);
+        let mut m = Device::load((0, 0, 0, 0));
+        for (i, opcode) in input.program.iter().enumerate() {
+            let instr = match mapping.get(&opcode.0) {
+                Some(&instr) => instr,
+                None => {
+                    println!("opcode {} never appears in the samples", opcode.0);
+                    return 0;
+                }
+            };
+            if let Err(f) = m.exec((instr, opcode.1, opcode.2, opcode.3)) {
+                println!("instruction {} failed: {:?}", i, f);
+                return 0;
+            }
         }
 
-        if mappings.len() == 16 {
-            break;
-        }
+        m.registers[0]
     }
-
-    mappings
 }
 
-pub struct Input {
-    tests: Vec<TestCase>,
-    program: Vec<(u8, u8, u8, u8)>,
-}
+// stop enumerating ambiguous mappings after this many
+const MAX_MAPPINGS: usize = 1000;
 
-#[derive(Debug)]
-pub struct TestCase {
-    before: (u64, u64, u64, u64),
-    after: (u64, u64, u64, u64),
-    opcode: (u8, u8, u8, u8),
+fn matching_instructions(tc: &TestCase) -> HashSet<DeviceInst> {
+    test_all_for_input(tc.before, tc.opcode)
+        .into_iter()
+        .filter(|&(_, r)| r == tc.after)
+        .map(|(i, _)| i)
+        .collect()
 }
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
-enum Inst {
-    Addr,
-    Addi,
-    Mulr,
-    Muli,
-    Banr,
-    Bani,
-    Borr,
-    Bori,
-    Setr,
-    Seti,
-    Gtir,
-    Gtri,
-    Gtrr,
-    Eqir,
-    Eqri,
-    Eqrr,
+enum Mapping<T> {
+    Unique(HashMap<u8, T>),
+    Ambiguous {
+        mappings: Vec<HashMap<u8, T>>,
+        truncated: bool,
+    },
+    Contradiction(Vec<Conflict>),
 }
 
-struct Machine {
-    registers: [u64; 4],
+// samples are referred to by their position in the list given to the solver
+#[derive(Debug)]
+enum Conflict {
+    // the sample behaves like no known instruction
+    NoMatch {
+        sample: usize,
+    },
+    // two samples of the same opcode share no instruction
+    Disjoint {
+        opcode: u8,
+        samples: (usize, usize),
+    },
+    // the samples of the opcode share no instruction, though no two of them are disjoint
+    Empty {
+        opcode: u8,
+        samples: Vec<usize>,
+    },
+    // these opcodes can only be mapped to fewer instructions than there are opcodes
+    Pigeonhole {
+        opcodes: Vec<u8>,
+        instructions: usize,
+        samples: Vec<usize>,
+    },
 }
 
-impl Machine {
-    fn load(values: (u64, u64, u64, u64)) -> Self {
-        Machine {
-            registers: [values.0, values.1, values.2, values.3],
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Conflict::NoMatch { sample } => {
+                write!(f, "sample {} matches no instruction", sample)
+            }
+            Conflict::Disjoint { opcode, samples } => write!(
+                f,
+                "opcode {}: samples {} and {} match no common instruction",
+                opcode, samples.0, samples.1
+            ),
+            Conflict::Empty { opcode, samples } => write!(
+                f,
+                "opcode {}: samples {:?} match no common instruction",
+                opcode, samples
+            ),
+            Conflict::Pigeonhole {
+                opcodes,
+                instructions,
+                samples,
+            } => write!(
+                f,
+                "opcodes {:?} can only be {} different instructions, see samples {:?}",
+                opcodes, instructions, samples
+            ),
         }
     }
+}
 
-    fn values(&self) -> (u64, u64, u64, u64) {
-        (
-            self.registers[0],
-            self.registers[1],
-            self.registers[2],
-            self.registers[3],
-        )
-    }
-
-    fn exec(&mut self, (r0, r1, r2, r3): (Inst, u8, u8, u8)) {
-        match r0 {
-            Inst::Addr => self.addr((0, r1, r2, r3)),
-            Inst::Addi => self.addi((0, r1, r2, r3)),
-            Inst::Mulr => self.mulr((0, r1, r2, r3)),
-            Inst::Muli => self.muli((0, r1, r2, r3)),
-            Inst::Banr => self.banr((0, r1, r2, r3)),
-            Inst::Bani => self.bani((0, r1, r2, r3)),
-            Inst::Borr => self.borr((0, r1, r2, r3)),
-            Inst::Bori => self.bori((0, r1, r2, r3)),
-            Inst::Setr => self.setr((0, r1, r2, r3)),
-            Inst::Seti => self.seti((0, r1, r2, r3)),
-            Inst::Gtir => self.gtir((0, r1, r2, r3)),
-            Inst::Gtri => self.gtri((0, r1, r2, r3)),
-            Inst::Gtrr => self.gtrr((0, r1, r2, r3)),
-            Inst::Eqir => self.eqir((0, r1, r2, r3)),
-            Inst::Eqri => self.eqri((0, r1, r2, r3)),
-            Inst::Eqrr => self.eqrr((0, r1, r2, r3)),
+// finds every way of giving each opcode number a distinct instruction consistent with
+// the samples, each sample being an opcode number and the instructions it behaves like
+//
+// numbers between 0 and the highest one sampled are all mapped, those without samples
+// can be any instruction
+fn solve_mapping<T: Copy + Eq + Hash>(
+    samples: &[(u8, HashSet<T>)],
+    instructions: &[T],
+    limit: usize,
+) -> Mapping<T> {
+    let n_opcodes = samples
+        .iter()
+        .map(|&(o, _)| o as usize + 1)
+        .max()
+        .unwrap_or(0);
+    let mut conflicts = vec![];
+
+    // candidates for each opcode, as indices in instructions
+    let mut candidates = vec![(0..instructions.len()).collect::<Vec<_>>(); n_opcodes];
+    let mut samples_of = vec![vec![]; n_opcodes];
+
+    for (i, (opcode, matches)) in samples.iter().enumerate() {
+        let opcode = *opcode as usize;
+        if matches.is_empty() {
+            conflicts.push(Conflict::NoMatch { sample: i });
+            continue;
         }
+        samples_of[opcode].push(i);
+        candidates[opcode].retain(|&c| matches.contains(&instructions[c]));
     }
 
-    fn addr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) {
-        let val = self.reg(r1) + self.reg(r2);
-        self.set_reg(r3, val);
-    }
-
-    fn addi(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) {
-        let val = self.reg(r1) + v2 as u64;
-        self.set_reg(r3, val);
-    }
+    for (opcode, cands) in candidates.iter().enumerate() {
+        if !cands.is_empty() || samples_of[opcode].is_empty() {
+            continue;
+        }
 
-    fn mulr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) {
-        let val = self.reg(r1) * self.reg(r2);
-        self.set_reg(r3, val);
+        let sampled = &samples_of[opcode];
+        let disjoint = sampled.iter().enumerate().find_map(|(k, &a)| {
+            sampled[k + 1..]
+                .iter()
+                .find(|&&b| samples[a].1.is_disjoint(&samples[b].1))
+                .map(|&b| (a, b))
+        });
+
+        conflicts.push(match disjoint {
+            Some(samples) => Conflict::Disjoint {
+                opcode: opcode as u8,
+                samples,
+            },
+            None => Conflict::Empty {
+                opcode: opcode as u8,
+                samples: sampled.clone(),
+            },
+        });
     }
 
-    fn muli(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) {
-        let val = self.reg(r1) * v2 as u64;
-        self.set_reg(r3, val);
+    if !conflicts.is_empty() {
+        return Mapping::Contradiction(conflicts);
     }
 
-    fn banr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) {
-        let val = self.reg(r1) & self.reg(r2);
-        self.set_reg(r3, val);
-    }
+    if let Some(opcodes) = hall_violation(&candidates, instructions.len()) {
+        let mut reachable = opcodes
+            .iter()
+            .flat_map(|&o| candidates[o].iter().cloned())
+            .collect::<Vec<_>>();
+        reachable.sort_unstable();
+        reachable.dedup();
 
-    fn bani(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) {
-        let val = self.reg(r1) & v2 as u64;
-        self.set_reg(r3, val);
+        let mut samples = opcodes
+            .iter()
+            .flat_map(|&o| samples_of[o].iter().cloned())
+            .collect::<Vec<_>>();
+        samples.sort_unstable();
+
+        return Mapping::Contradiction(vec![Conflict::Pigeonhole {
+            opcodes: opcodes.iter().map(|&o| o as u8).collect(),
+            instructions: reachable.len(),
+            samples,
+        }]);
     }
 
-    fn borr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) {
-        let val = self.reg(r1) | self.reg(r2);
-        self.set_reg(r3, val);
+    // most constrained opcodes first
+    let mut order = (0..n_opcodes).collect::<Vec<_>>();
+    order.sort_by_key(|&o| candidates[o].len());
+
+    let mut found = vec![];
+    let mut assigned = vec![None; n_opcodes];
+    let mut used = vec![false; instructions.len()];
+    enumerate_mappings(
+        &order,
+        &candidates,
+        &mut assigned,
+        &mut used,
+        &mut found,
+        limit + 1,
+    );
+
+    let mut mappings = found
+        .into_iter()
+        .map(|assigned| {
+            assigned
+                .into_iter()
+                .enumerate()
+                .map(|(o, c)| (o as u8, instructions[c]))
+                .collect::<HashMap<_, _>>()
+        })
+        .collect::<Vec<_>>();
+
+    if mappings.len() == 1 {
+        Mapping::Unique(mappings.remove(0))
+    } else {
+        let truncated = mappings.len() > limit;
+        mappings.truncate(limit);
+        Mapping::Ambiguous {
+            mappings,
+            truncated,
+        }
     }
+}
 
-    fn bori(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) {
-        let val = self.reg(r1) | v2 as u64;
-        self.set_reg(r3, val);
-    }
+fn enumerate_mappings(
+    order: &[usize],
+    candidates: &[Vec<usize>],
+    assigned: &mut [Option<usize>],
+    used: &mut [bool],
+    found: &mut Vec<Vec<usize>>,
+    limit: usize,
+) {
+    let (&opcode, rest) = match order.split_first() {
+        Some(first) => first,
+        None => {
+            found.push(assigned.iter().map(|c| c.unwrap()).collect());
+            return;
+        }
+    };
 
-    fn setr(&mut self, (_, r1, _, r3): (u8, u8, u8, u8)) {
-        let val = self.reg(r1);
-        self.set_reg(r3, val);
-    }
+    for &c in candidates[opcode].iter() {
+        if found.len() >= limit {
+            return;
+        }
+        if used[c] {
+            continue;
+        }
 
-    fn seti(&mut self, (_, v1, _, r3): (u8, u8, u8, u8)) {
-        self.set_reg(r3, v1 as u64);
+        used[c] = true;
+        assigned[opcode] = Some(c);
+        enumerate_mappings(rest, candidates, assigned, used, found, limit);
+        assigned[opcode] = None;
+        used[c] = false;
     }
+}
 
-    fn gtir(&mut self, (_, v1, r2, r3): (u8, u8, u8, u8)) {
-        let val = if v1 as u64 > self.reg(r2) { 1 } else { 0 };
-        self.set_reg(r3, val);
+// when no mapping exists, returns a set of opcodes that have fewer candidate
+// instructions between them than there are opcodes in the set
+fn hall_violation(candidates: &[Vec<usize>], n_instructions: usize) -> Option<Vec<usize>> {
+    // maximum matching, owner[c] being the opcode given instruction c
+    let mut owner = vec![None; n_instructions];
+    let mut unmatched = None;
+
+    for opcode in 0..candidates.len() {
+        let mut seen = vec![false; n_instructions];
+        if !augment(opcode, candidates, &mut seen, &mut owner) && unmatched.is_none() {
+            unmatched = Some(opcode);
+        }
     }
 
-    fn gtri(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) {
-        let val = if self.reg(r1) > v2 as u64 { 1 } else { 0 };
-        self.set_reg(r3, val);
+    // opcodes reachable from an unmatched one through alternating paths
+    // only have the instructions owned by the others as candidates
+    let mut opcodes = vec![unmatched?];
+    let mut seen = vec![false; n_instructions];
+    let mut i = 0;
+    while i < opcodes.len() {
+        for &c in candidates[opcodes[i]].iter() {
+            if !seen[c] {
+                seen[c] = true;
+                if let Some(o) = owner[c] {
+                    opcodes.push(o);
+                }
+            }
+        }
+        i += 1;
     }
 
-    fn gtrr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) {
-        let val = if self.reg(r1) > self.reg(r2) { 1 } else { 0 };
-        self.set_reg(r3, val);
-    }
+    opcodes.sort_unstable();
+    Some(opcodes)
+}
 
-    fn eqir(&mut self, (_, v1, r2, r3): (u8, u8, u8, u8)) {
-        let val = if v1 as u64 == self.reg(r2) { 1 } else { 0 };
-        self.set_reg(r3, val);
-    }
+fn augment(
+    opcode: usize,
+    candidates: &[Vec<usize>],
+    seen: &mut [bool],
+    owner: &mut [Option<usize>],
+) -> bool {
+    for &c in candidates[opcode].iter() {
+        if seen[c] {
+            continue;
+        }
+        seen[c] = true;
 
-    fn eqri(&mut self, (_, r1, v2, r3): (u8, u8, u8, u8)) {
-        let val = if self.reg(r1) == v2 as u64 { 1 } else { 0 };
-        self.set_reg(r3, val);
+        let free = match owner[c] {
+            None => true,
+            Some(other) => augment(other, candidates, seen, owner),
+        };
+        if free {
+            owner[c] = Some(opcode);
+            return true;
+        }
     }
 
-    fn eqrr(&mut self, (_, r1, r2, r3): (u8, u8, u8, u8)) {
-        let val = if self.reg(r1) == self.reg(r2) { 1 } else { 0 };
-        self.set_reg(r3, val);
-    }
+    false
+}
 
-    fn reg(&self, n: u8) -> u64 {
-        self.registers[n as usize]
-    }
+pub struct Input {
+    tests: Vec<TestCase>,
+    program: Vec<(u8, u8, u8, u8)>,
+}
 
-    fn set_reg(&mut self, n: u8, val: u64) {
-        self.registers[n as usize] = val;
-    }
+#[derive(Debug)]
+pub struct TestCase {
+    before: (u64, u64, u64, u64),
+    after: (u64, u64, u64, u64),
+    opcode: (u8, u8, u8, u8),
 }
 
 fn test_all_for_input(
     input: (u64, u64, u64, u64),
     opcode: (u8, u8, u8, u8),
-) -> Vec<(Inst, (u64, u64, u64, u64))> {
-    let mut output = vec![];
-
-    let mut cpu = Machine::load(input);
-    cpu.addr(opcode);
-    output.push((Inst::Addr, cpu.values()));
-
-    cpu = Machine::load(input);
-    cpu.addi(opcode);
-    output.push((Inst::Addi, cpu.values()));
-
-    cpu = Machine::load(input);
-    cpu.mulr(opcode);
-    output.push((Inst::Mulr, cpu.values()));
-
-    cpu = Machine::load(input);
-    cpu.muli(opcode);
-    output.push((Inst::Muli, cpu.values()));
-
-    cpu = Machine::load(input);
-    cpu.banr(opcode);
-    output.push((Inst::Banr, cpu.values()));
-
-    cpu = Machine::load(input);
-    cpu.bani(opcode);
-    output.push((Inst::Bani, cpu.values()));
+) -> Vec<(DeviceInst, (u64, u64, u64, u64))> {
+    // instructions failing on the input cannot match the sample
+    DEVICE_INSTS
+        .iter()
+        .filter_map(|&inst| {
+            let mut cpu = Device::load(input);
+            cpu.exec((inst, opcode.1, opcode.2, opcode.3)).ok()?;
+            Some((inst, cpu.values()))
+        })
+        .collect()
+}
 
-    cpu = Machine::load(input);
-    cpu.borr(opcode);
-    output.push((Inst::Borr, cpu.values()));
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    cpu = Machine::load(input);
-    cpu.bori(opcode);
-    output.push((Inst::Bori, cpu.values()));
+    const INSTRUCTIONS: [char; 3] = ['a', 'b', 'c'];
 
-    cpu = Machine::load(input);
-    cpu.setr(opcode);
-    output.push((Inst::Setr, cpu.values()));
+    fn samples(samples: &[(u8, &str)]) -> Vec<(u8, HashSet<char>)> {
+        samples
+            .iter()
+            .map(|&(opcode, matches)| (opcode, matches.chars().collect()))
+            .collect()
+    }
 
-    cpu = Machine::load(input);
-    cpu.seti(opcode);
-    output.push((Inst::Seti, cpu.values()));
+    fn conflicts(samples: &[(u8, HashSet<char>)]) -> Vec<Conflict> {
+        match solve_mapping(samples, &INSTRUCTIONS, 10) {
+            Mapping::Contradiction(conflicts) => conflicts,
+            _ => panic!("expected a contradiction"),
+        }
+    }
 
-    cpu = Machine::load(input);
-    cpu.gtir(opcode);
-    output.push((Inst::Gtir, cpu.values()));
+    #[test]
+    fn unique() {
+        let samples = samples(&[(0, "a"), (1, "ab"), (2, "abc"), (1, "bc")]);
+        match solve_mapping(&samples, &INSTRUCTIONS, 10) {
+            Mapping::Unique(m) => {
+                assert_eq!((m[&0], m[&1], m[&2]), ('a', 'b', 'c'));
+            }
+            _ => panic!("expected a unique mapping"),
+        }
+    }
 
-    cpu = Machine::load(input);
-    cpu.gtri(opcode);
-    output.push((Inst::Gtri, cpu.values()));
+    #[test]
+    fn ambiguous() {
+        // opcode 1 has no sample, so it can be anything left
+        let samples = samples(&[(0, "a"), (2, "bc")]);
+        match solve_mapping(&samples, &INSTRUCTIONS, 10) {
+            Mapping::Ambiguous {
+                mappings,
+                truncated,
+            } => {
+                assert_eq!(mappings.len(), 2);
+                assert!(!truncated);
+            }
+            _ => panic!("expected several mappings"),
+        }
 
-    cpu = Machine::load(input);
-    cpu.gtrr(opcode);
-    output.push((Inst::Gtrr, cpu.values()));
+        match solve_mapping(&samples, &INSTRUCTIONS, 1) {
+            Mapping::Ambiguous {
+                mappings,
+                truncated,
+            } => {
+                assert_eq!(mappings.len(), 1);
+                assert!(truncated);
+            }
+            _ => panic!("expected several mappings"),
+        }
+    }
 
-    cpu = Machine::load(input);
-    cpu.eqir(opcode);
-    output.push((Inst::Eqir, cpu.values()));
+    #[test]
+    fn no_match() {
+        let conflicts = conflicts(&samples(&[(0, "a"), (1, "")]));
+        assert!(matches!(conflicts[..], [Conflict::NoMatch { sample: 1 }]));
+    }
 
-    cpu = Machine::load(input);
-    cpu.eqri(opcode);
-    output.push((Inst::Eqri, cpu.values()));
+    #[test]
+    fn disjoint() {
+        let conflicts = conflicts(&samples(&[(0, "ab"), (1, "a"), (1, "bc")]));
+        assert!(matches!(
+            conflicts[..],
+            [Conflict::Disjoint {
+                opcode: 1,
+                samples: (1, 2)
+            }]
+        ));
+    }
 
-    cpu = Machine::load(input);
-    cpu.eqrr(opcode);
-    output.push((Inst::Eqrr, cpu.values()));
+    #[test]
+    fn empty() {
+        let conflicts = conflicts(&samples(&[(0, "ab"), (0, "bc"), (0, "ac")]));
+        match &conflicts[..] {
+            [Conflict::Empty { opcode, samples }] => {
+                assert_eq!(*opcode, 0);
+                assert_eq!(samples, &[0, 1, 2]);
+            }
+            c => panic!("unexpected conflicts: {:?}", c),
+        }
+    }
 
-    output
+    #[test]
+    fn pigeonhole() {
+        let conflicts = conflicts(&samples(&[(0, "a"), (1, "a"), (2, "abc")]));
+        match &conflicts[..] {
+            [Conflict::Pigeonhole {
+                opcodes,
+                instructions,
+                samples,
+            }] => {
+                assert_eq!(opcodes, &[0, 1]);
+                assert_eq!(*instructions, 1);
+                assert_eq!(samples, &[0, 1]);
+            }
+            c => panic!("unexpected conflicts: {:?}", c),
+        }
+    }
 }
@@ -1,196 +1,176 @@
-use crate::solver::Solver;
+use crate::{
+    grid::Grid,
+    point::{Direction, Point},
+    solver::Solver,
+};
 use regex::Regex;
 use std::{
     collections::VecDeque,
-    fmt::{Debug, Error, Formatter, Write},
+    convert::TryFrom,
     io::{self, BufRead, BufReader},
-    iter::repeat,
 };
 
 pub struct Problem;
 
 impl Solver for Problem {
-    type Input = Vec<Pt>;
+    type Input = Vec<Point>;
     type Output1 = u64;
     type Output2 = u64;
 
-    fn parse_input<R: io::Read>(&self, r: R) -> Vec<Pt> {
+    fn parse_input<R: io::Read>(&self, r: R) -> Vec<Point> {
         coords_from_reader(r)
     }
 
-    fn solve_first(&self, input: &Vec<Pt>) -> u64 {
+    fn solve_first(&self, input: &Vec<Point>) -> u64 {
         let bounds = coords_bounds(&input).unwrap();
-        let mut grid = Grid::with_bounds(bounds);
+        let mut grid = Scan::with_bounds(bounds);
         setup_grid(&mut grid, &input);
 
-        let starting_point = Pt { x: 500, y: 0 };
+        let starting_point = Point::new(500, 0);
 
         {
             let mut filler = GridFiller::new(&mut grid);
-            filler.exec(&starting_point);
+            filler.exec(starting_point);
         }
 
-        //println!("{:?}", grid);
+        //println!("{}", grid.cells);
 
-        grid.cells
-            .iter()
-            .skip(grid.bounds.min_y)
+        (grid.bounds.min_y..=grid.bounds.max_y)
+            .filter_map(|y| grid.cells.row(y as usize))
             .flatten()
-            .map(|&b| if b == b'|' || b == b'~' { 1 } else { 0 })
-            .sum()
+            .filter(|&&b| b == b'|' || b == b'~')
+            .count() as u64
     }
 
-    fn solve_second(&self, input: &Vec<Pt>) -> u64 {
+    fn solve_second(&self, input: &Vec<Point>) -> u64 {
         let bounds = coords_bounds(&input).unwrap();
-        let mut grid = Grid::with_bounds(bounds);
+        let mut grid = Scan::with_bounds(bounds);
         setup_grid(&mut grid, &input);
 
-        let starting_point = Pt { x: 500, y: 0 };
+        let starting_point = Point::new(500, 0);
 
         {
             let mut filler = GridFiller::new(&mut grid);
-            filler.exec(&starting_point);
+            filler.exec(starting_point);
         }
 
-        //println!("{:?}", grid);
+        //println!("{}", grid.cells);
 
-        grid.cells
-            .iter()
-            .skip(grid.bounds.min_y)
+        (grid.bounds.min_y..=grid.bounds.max_y)
+            .filter_map(|y| grid.cells.row(y as usize))
             .flatten()
-            .map(|&b| if b == b'~' { 1 } else { 0 })
-            .sum()
+            .filter(|&&b| b == b'~')
+            .count() as u64
     }
 }
 
-struct Grid {
-    cells: Vec<Vec<u8>>,
+// the scanned slice of ground, the grid starts at x = min_x
+struct Scan {
+    cells: Grid<u8>,
     bounds: Bounds,
 }
 
-impl Grid {
+impl Scan {
     fn with_bounds(bounds: Bounds) -> Self {
-        let width = bounds.max_x - bounds.min_x + 1;
-        let height = bounds.max_y + 1;
+        let width = (bounds.max_x - bounds.min_x + 1) as usize;
+        let height = (bounds.max_y + 1) as usize;
 
-        let cells = repeat(repeat(b'.').take(width).collect())
-            .take(height)
-            .collect();
+        Self {
+            cells: Grid::new(width, height, b'.'),
+            bounds,
+        }
+    }
 
-        Self { cells, bounds }
+    fn to_grid(&self, pt: Point) -> Point {
+        pt - Point::new(self.bounds.min_x, 0)
     }
 
-    fn set(&mut self, pt: &Pt, val: u8) {
-        let x = pt.x.saturating_sub(self.bounds.min_x);
-        self.cells[pt.y][x] = val;
+    fn set(&mut self, pt: Point, val: u8) {
+        let pt = self.to_grid(pt);
+        self.cells.set(pt, val);
     }
 
-    fn get(&self, pt: &Pt) -> u8 {
-        let x = pt.x.saturating_sub(self.bounds.min_x);
-        self.cells[pt.y][x]
+    fn get(&self, pt: Point) -> u8 {
+        self.cells.get(self.to_grid(pt)).cloned().unwrap_or(b'.')
     }
-}
 
-impl Debug for Grid {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        for row in self.cells.iter() {
-            for b in row.iter() {
-                f.write_char(*b as char)?;
-            }
-            f.write_char('\n')?;
-        }
-        Ok(())
+    // the first cell under this point that is not sand, if any
+    fn first_below(&self, pt: Point) -> Option<(Point, u8)> {
+        let x = usize::try_from(self.to_grid(pt).x).ok()?;
+        self.cells
+            .column(x)?
+            .enumerate()
+            .skip(pt.y as usize + 1)
+            .find(|&(_, &c)| c != b'.')
+            .map(|(y, &c)| (Point::new(pt.x, y as i64), c))
     }
 }
 
-fn setup_grid(grid: &mut Grid, coords: &[Pt]) {
-    for p in coords {
+fn setup_grid(grid: &mut Scan, coords: &[Point]) {
+    for &p in coords {
         grid.set(p, b'#');
     }
 }
 
 enum Search {
-    Down(Pt),
-    Sides(Pt),
+    Down(Point),
+    Sides(Point),
 }
 
 struct GridFiller<'a> {
-    grid: &'a mut Grid,
+    grid: &'a mut Scan,
     queue: VecDeque<Search>,
 }
 
 impl<'a> GridFiller<'a> {
-    fn new(grid: &'a mut Grid) -> Self {
+    fn new(grid: &'a mut Scan) -> Self {
         Self {
             grid,
             queue: VecDeque::new(),
         }
     }
 
-    fn exec(&mut self, starting_pt: &Pt) {
-        self.queue.push_back(Search::Down(starting_pt.clone()));
-        self.grid.set(&starting_pt, b'X');
+    fn exec(&mut self, starting_pt: Point) {
+        self.queue.push_back(Search::Down(starting_pt));
+        self.grid.set(starting_pt, b'X');
 
         while let Some(s) = self.queue.pop_front() {
             match s {
-                Search::Down(pt) => self.fill_down(&pt),
-                Search::Sides(pt) => self.fill_sides(&pt),
+                Search::Down(pt) => self.fill_down(pt),
+                Search::Sides(pt) => self.fill_sides(pt),
             }
-            //println!("{:?}", self.grid);
+            //println!("{}", self.grid.cells);
         }
     }
 
-    fn fill_down(&mut self, pt: &Pt) {
-        let mut pt = pt.clone();
-        loop {
-            // one step down
-            pt = Pt {
-                x: pt.x,
-                y: pt.y + 1,
-            };
-
-            // stop if we are outside the grid
-            if pt.y > self.grid.bounds.max_y {
-                return;
-            }
+    fn fill_down(&mut self, pt: Point) {
+        // water falls until it reaches something, or leaves the grid
+        let below = self.grid.first_below(pt);
+        let end_y = below
+            .as_ref()
+            .map_or(self.grid.bounds.max_y + 1, |(p, _)| p.y);
+        for y in pt.y + 1..end_y {
+            self.grid.set(Point::new(pt.x, y), b'|');
+        }
 
-            let cell = self.grid.get(&pt);
-            match cell {
-                b'.' => {
-                    // empty, let's waterize it
-                    self.grid.set(&pt, b'|');
-                }
-                b'#' | b'~' => {
-                    // clay or still water, we need to start searching left and right
-                    self.queue.push_back(Search::Sides(Pt {
-                        x: pt.x,
-                        y: pt.y - 1,
-                    }));
-                    return;
-                }
-                _ => {
-                    // if we're finding eg. '|' or '~' or nothing, just stop
-                    return;
-                }
-            }
+        // clay or still water, we need to start searching left and right
+        // if we're finding eg. '|', just stop
+        if let Some((p, b'#')) | Some((p, b'~')) = below {
+            self.queue.push_back(Search::Sides(p + Direction::Up));
         }
     }
 
-    fn fill_sides(&mut self, pt: &Pt) {
+    fn fill_sides(&mut self, pt: Point) {
         // case our cell has been waterized already, go above
         if self.grid.get(pt) == b'~' {
-            let above = Pt {
-                x: pt.x,
-                y: pt.y - 1,
-            };
-            self.queue.push_back(Search::Sides(above));
+            self.queue.push_back(Search::Sides(pt + Direction::Up));
             return;
         }
 
         // loop left and right
         // while point under is water and next is empty
-        let border_left = self.fill_side(pt, -1);
-        let border_right = self.fill_side(pt, 1);
+        let border_left = self.fill_side(pt, Direction::Left);
+        let border_right = self.fill_side(pt, Direction::Right);
 
         // if we have both borders, we set all points in between to still water ~
         // and then do a fill sides for the point above
@@ -199,43 +179,30 @@ impl<'a> GridFiller<'a> {
             (Some(left), Some(right)) => {
                 // make water still
                 for x in left.x + 1..right.x {
-                    let pt = Pt { x, y: left.y };
-                    self.grid.set(&pt, b'~');
+                    self.grid.set(Point::new(x, left.y), b'~');
                 }
 
-                let above = Pt {
-                    x: pt.x,
-                    y: pt.y - 1,
-                };
-                self.queue.push_back(Search::Sides(above));
+                self.queue.push_back(Search::Sides(pt + Direction::Up));
             }
             _ => {
-                self.grid.set(&pt, b'|');
+                self.grid.set(pt, b'|');
             }
         }
     }
 
-    fn fill_side(&mut self, pt: &Pt, dx: isize) -> Option<Pt> {
+    fn fill_side(&mut self, pt: Point, dir: Direction) -> Option<Point> {
         // return border point if we are in a tank
-        let mut pt = pt.clone();
+        let mut pt = pt;
         loop {
-            pt = Pt {
-                x: (pt.x as isize + dx) as usize,
-                y: pt.y,
-            };
-
-            let pt_under = Pt {
-                x: pt.x,
-                y: pt.y + 1,
-            };
+            pt += dir;
 
-            let cell = self.grid.get(&pt);
-            let cell_under = self.grid.get(&pt_under);
+            let cell = self.grid.get(pt);
+            let cell_under = self.grid.get(pt + Direction::Down);
 
             match (cell, cell_under) {
                 (b'.', b'#') | (b'.', b'~') => {
                     // empty cell, has floor, waterize and continue
-                    self.grid.set(&pt, b'|');
+                    self.grid.set(pt, b'|');
                 }
                 (b'#', _) => {
                     // stop here
@@ -250,12 +217,12 @@ impl<'a> GridFiller<'a> {
                 }
                 (b'.', b'.') | (b'|', b'.') => {
                     // we're outside the tank, initialize a new down fill and quit
-                    self.grid.set(&pt, b'|');
+                    self.grid.set(pt, b'|');
                     self.queue.push_back(Search::Down(pt));
                     return None;
                 }
                 (a, b) => {
-                    println!("{:?}", self.grid);
+                    println!("{}", self.grid.cells);
                     println!("{:?}", pt);
                     panic!("we should not be here: {}, {}", a as char, b as char)
                 }
@@ -264,23 +231,23 @@ impl<'a> GridFiller<'a> {
     }
 }
 
-fn points_from_line(line: &str, (re_x, re_y): (&Regex, &Regex)) -> Option<Vec<Pt>> {
+fn points_from_line(line: &str, (re_x, re_y): (&Regex, &Regex)) -> Option<Vec<Point>> {
     if let Some(caps) = re_x.captures(line) {
-        let x = caps.get(1)?.as_str().parse::<usize>().ok()?;
-        let y1 = caps.get(2)?.as_str().parse::<usize>().ok()?;
-        let y2 = caps.get(3)?.as_str().parse::<usize>().ok()?;
-        Some((y1..=y2).map(|y| Pt { x, y }).collect())
+        let x = caps.get(1)?.as_str().parse::<i64>().ok()?;
+        let y1 = caps.get(2)?.as_str().parse::<i64>().ok()?;
+        let y2 = caps.get(3)?.as_str().parse::<i64>().ok()?;
+        Some((y1..=y2).map(|y| Point::new(x, y)).collect())
     } else if let Some(caps) = re_y.captures(line) {
-        let y = caps.get(1)?.as_str().parse::<usize>().ok()?;
-        let x1 = caps.get(2)?.as_str().parse::<usize>().ok()?;
-        let x2 = caps.get(3)?.as_str().parse::<usize>().ok()?;
-        Some((x1..=x2).map(|x| Pt { x, y }).collect())
+        let y = caps.get(1)?.as_str().parse::<i64>().ok()?;
+        let x1 = caps.get(2)?.as_str().parse::<i64>().ok()?;
+        let x2 = caps.get(3)?.as_str().parse::<i64>().ok()?;
+        Some((x1..=x2).map(|x| Point::new(x, y)).collect())
     } else {
         None
     }
 }
 
-fn coords_from_reader<R: io::Read>(r: R) -> Vec<Pt> {
+fn coords_from_reader<R: io::Read>(r: R) -> Vec<Point> {
     let re_x = Regex::new(r"x=(\d+), y=(\d+)\.\.(\d+)").unwrap();
     let re_y = Regex::new(r"y=(\d+), x=(\d+)\.\.(\d+)").unwrap();
     BufReader::new(r)
@@ -291,7 +258,7 @@ fn coords_from_reader<R: io::Read>(r: R) -> Vec<Pt> {
         .collect()
 }
 
-fn coords_bounds(coords: &[Pt]) -> Option<Bounds> {
+fn coords_bounds(coords: &[Point]) -> Option<Bounds> {
     Some(Bounds {
         min_x: coords.iter().map(|p| p.x).min()? - 1,
         min_y: coords.iter().map(|p| p.y).min()?,
@@ -302,14 +269,8 @@ fn coords_bounds(coords: &[Pt]) -> Option<Bounds> {
 
 #[derive(Debug)]
 struct Bounds {
-    min_x: usize,
-    min_y: usize,
-    max_x: usize,
-    max_y: usize,
-}
-
-#[derive(Clone, Debug)]
-pub struct Pt {
-    x: usize,
-    y: usize,
+    min_x: i64,
+    min_y: i64,
+    max_x: i64,
+    max_y: i64,
 }
@@ -1,240 +1,91 @@
-use crate::solver::Solver;
-use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
-    fmt::{Debug, Error, Formatter, Write},
-    hash::{Hash, Hasher},
-    io::{self, BufRead, BufReader},
-    iter::repeat,
+use crate::{
+    automaton::{Automaton, Cell},
+    cycle::simulate,
+    grid::Grid,
+    solver::Solver,
 };
+use std::io;
 
 pub struct Problem;
 
 impl Solver for Problem {
-    type Input = Grid;
+    type Input = Grid<Acre>;
     type Output1 = u64;
     type Output2 = u64;
 
-    fn parse_input<R: io::Read>(&self, r: R) -> Grid {
-        Grid::from_reader(r)
+    fn parse_input<R: io::Read>(&self, r: R) -> Grid<Acre> {
+        Grid::from_reader(r, |c| match c {
+            '.' => Some(Acre::Open),
+            '|' => Some(Acre::Trees),
+            '#' => Some(Acre::Lumberyard),
+            _ => None,
+        })
+        .expect("unable to parse grid")
     }
 
-    fn solve_first(&self, input: &Grid) -> u64 {
-        let mut g = input.clone();
-
+    fn solve_first(&self, input: &Grid<Acre>) -> u64 {
+        let mut area = Automaton::new(input.clone());
         for _ in 0..10 {
-            g = g.next_minute();
-            //println!("{:?}", g);
+            area.step(next_minute);
         }
+        //println!("{}", area.cells());
 
-        let n_trees: u64 = g
-            .cells
-            .iter()
-            .flatten()
-            .map(|&b| if b == b'|' { 1 } else { 0 })
-            .sum();
-        let n_lumbs: u64 = g
-            .cells
-            .iter()
-            .flatten()
-            .map(|&b| if b == b'#' { 1 } else { 0 })
-            .sum();
-
-        n_trees * n_lumbs
+        resource_value(area.cells())
     }
 
-    fn solve_second(&self, input: &Grid) -> u64 {
-        let mut g = input.clone();
-        let mut checksums = HashMap::new();
-
-        // find the first pattern that gets repeated, and the repetition period
-        let mut period = 0;
-        let mut first_repeat_minute = 0;
-        for minute in 1..=1_000_000_000 {
-            g = g.next_minute();
-            //println!("{:?}", g);
-
-            let mut s = DefaultHasher::new();
-            g.hash(&mut s);
-            let h = s.finish();
-
-            if let Some(min) = checksums.get(&h) {
-                period = minute - min;
-                first_repeat_minute = *min;
-
-                /*println!("First Minute: {}", first_repeat_minute);
-                println!("Minute:       {}", minute);
-                println!("Period:       {}", period);*/
-
-                break;
-            }
-
-            checksums.insert(h, minute);
-        }
-
-        // warp to the last minute before 1000000000 that has this pattern
-        let cycles = (1_000_000_000 - first_repeat_minute) / period;
-        let warp_to = first_repeat_minute + period * cycles;
-        for _minute in warp_to + 1..=1_000_000_000 {
-            g = g.next_minute();
-        }
-
-        let n_trees: u64 = g
-            .cells
-            .iter()
-            .flatten()
-            .map(|&b| if b == b'|' { 1 } else { 0 })
-            .sum();
-        let n_lumbs: u64 = g
-            .cells
-            .iter()
-            .flatten()
-            .map(|&b| if b == b'#' { 1 } else { 0 })
-            .sum();
+    fn solve_second(&self, input: &Grid<Acre>) -> u64 {
+        // the landscape ends up repeating itself, simulate skips the repetitions
+        let area = simulate(
+            Automaton::new(input.clone()),
+            1_000_000_000,
+            |a| a.step(next_minute),
+            |a| a.cells().clone(),
+        );
 
-        n_trees * n_lumbs
+        resource_value(area.cells())
     }
 }
 
-#[derive(Clone, Hash)]
-pub struct Grid {
-    cells: Vec<Vec<u8>>,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Acre {
+    Open,
+    Trees,
+    Lumberyard,
 }
 
-impl Grid {
-    fn from_reader<R: io::Read>(r: R) -> Self {
-        let cells = BufReader::new(r)
-            .lines()
-            .filter_map(|l| l.ok())
-            .map(|l| l.into_bytes())
-            .collect();
-
-        Self { cells }
-    }
-
-    fn next_minute(self) -> Grid {
-        let height = self.cells.len();
-        let width = self.cells[0].len();
-
-        let mut cells: Vec<Vec<u8>> = repeat(repeat(b'.').take(width).collect())
-            .take(height)
-            .collect();
+impl Cell for Acre {
+    const KINDS: usize = 3;
 
-        for y in 0..self.cells.len() {
-            for x in 0..self.cells[0].len() {
-                let c = self.cells[y][x];
-                let p = Pt { x, y };
-                let nc = match c {
-                    b'.' => self.next_open(&p),
-                    b'|' => self.next_tree(&p),
-                    b'#' => self.next_lumberyard(&p),
-                    _ => panic!("should not be here"),
-                };
-                cells[y][x] = nc;
-            }
-        }
-
-        Grid { cells }
-    }
-
-    fn next_open(&self, p: &Pt) -> u8 {
-        let n_trees: usize = self
-            .neighbours(p)
-            .iter()
-            .map(|&b| if b == b'|' { 1 } else { 0 })
-            .sum();
-
-        if n_trees >= 3 {
-            b'|'
-        } else {
-            b'.'
-        }
+    fn kind(self) -> usize {
+        self as usize
     }
+}
 
-    fn next_tree(&self, p: &Pt) -> u8 {
-        let n_lumbs: usize = self
-            .neighbours(p)
-            .iter()
-            .map(|&b| if b == b'#' { 1 } else { 0 })
-            .sum();
-
-        if n_lumbs >= 3 {
-            b'#'
-        } else {
-            b'|'
+impl From<Acre> for char {
+    fn from(a: Acre) -> char {
+        match a {
+            Acre::Open => '.',
+            Acre::Trees => '|',
+            Acre::Lumberyard => '#',
         }
     }
+}
 
-    fn next_lumberyard(&self, p: &Pt) -> u8 {
-        let n_trees: usize = self
-            .neighbours(p)
-            .iter()
-            .map(|&b| if b == b'|' { 1 } else { 0 })
-            .sum();
-        let n_lumbs: usize = self
-            .neighbours(p)
-            .iter()
-            .map(|&b| if b == b'#' { 1 } else { 0 })
-            .sum();
-
-        if n_trees >= 1 && n_lumbs >= 1 {
-            b'#'
-        } else {
-            b'.'
-        }
-    }
+fn next_minute(acre: Acre, counts: &[usize]) -> Acre {
+    let n_trees = counts[Acre::Trees.kind()];
+    let n_lumbs = counts[Acre::Lumberyard.kind()];
 
-    fn get(&self, x: usize, y: usize) -> Option<u8> {
-        self.cells.get(y)?.get(x).cloned()
-    }
-
-    fn neighbours(&self, pt: &Pt) -> Vec<u8> {
-        let height = self.cells.len();
-        let width = self.cells[0].len();
-        let mut v = vec![];
-
-        if pt.x > 0 && pt.y > 0 {
-            v.push(self.get(pt.x - 1, pt.y - 1));
-        }
-        if pt.y > 0 {
-            v.push(self.get(pt.x, pt.y - 1));
-        }
-        if pt.x < width && pt.y > 0 {
-            v.push(self.get(pt.x + 1, pt.y - 1));
-        }
-        if pt.x > 0 {
-            v.push(self.get(pt.x - 1, pt.y));
-        }
-        if pt.x < width {
-            v.push(self.get(pt.x + 1, pt.y));
-        }
-        if pt.x > 0 && pt.y < height {
-            v.push(self.get(pt.x - 1, pt.y + 1));
-        }
-        if pt.y < height {
-            v.push(self.get(pt.x, pt.y + 1));
-        }
-        if pt.x < width && pt.y < height {
-            v.push(self.get(pt.x + 1, pt.y + 1));
-        }
-
-        v.iter().filter_map(|&v| v).collect()
+    match acre {
+        Acre::Open if n_trees >= 3 => Acre::Trees,
+        Acre::Trees if n_lumbs >= 3 => Acre::Lumberyard,
+        Acre::Lumberyard if n_trees == 0 || n_lumbs == 0 => Acre::Open,
+        a => a,
     }
 }
 
-impl Debug for Grid {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        for row in self.cells.iter() {
-            for b in row.iter() {
-                f.write_char(*b as char)?;
-            }
-            f.write_char('\n')?;
-        }
-        Ok(())
-    }
-}
+fn resource_value(g: &Grid<Acre>) -> u64 {
+    let n_trees = g.iter().filter(|&&a| a == Acre::Trees).count() as u64;
+    let n_lumbs = g.iter().filter(|&&a| a == Acre::Lumberyard).count() as u64;
 
-#[derive(Clone, Debug)]
-struct Pt {
-    x: usize,
-    y: usize,
+    n_trees * n_lumbs
 }
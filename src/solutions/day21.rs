@@ -1,6 +1,8 @@
-use crate::solver::Solver;
-use regex::Regex;
-use std::io::{self, BufRead, BufReader};
+use crate::{
+    elfcode::{Compiled, Machine, Opcode, Program},
+    solver::Solver,
+};
+use std::{collections::HashSet, io};
 
 pub struct Problem;
 
@@ -14,272 +16,69 @@ impl Solver for Problem {
     }
 
     fn solve_first(&self, input: &Program) -> u64 {
-        for i in 1..60_000_000 {
-            let mut vm = Machine::new();
-            vm.registers[0] = i;
-
-            let n = input.execute(&mut vm);
-
-            //println!("{} => {}", i, n);
-
-            if n < 12000 {
-                return i;
-            }
-        }
-        0
+        HaltingValues::new(input).next().unwrap_or(0)
     }
 
-    fn solve_second(&self, _input: &Program) -> u64 {
-        0
+    fn solve_second(&self, input: &Program) -> u64 {
+        HaltingValues::new(input).last().unwrap_or(0)
     }
 }
 
-#[derive(Debug)]
-pub enum Opcode {
-    Addr,
-    Addi,
-    Mulr,
-    Muli,
-    Banr,
-    Bani,
-    Borr,
-    Bori,
-    Setr,
-    Seti,
-    Gtir,
-    Gtri,
-    Gtrr,
-    Eqir,
-    Eqri,
-    Eqrr,
-}
-
-impl Opcode {
-    fn parse(s: &str) -> Option<Self> {
-        match s {
-            "addr" => Some(Opcode::Addr),
-            "addi" => Some(Opcode::Addi),
-            "mulr" => Some(Opcode::Mulr),
-            "muli" => Some(Opcode::Muli),
-            "banr" => Some(Opcode::Banr),
-            "bani" => Some(Opcode::Bani),
-            "borr" => Some(Opcode::Borr),
-            "bori" => Some(Opcode::Bori),
-            "setr" => Some(Opcode::Setr),
-            "seti" => Some(Opcode::Seti),
-            "gtir" => Some(Opcode::Gtir),
-            "gtri" => Some(Opcode::Gtri),
-            "gtrr" => Some(Opcode::Gtrr),
-            "eqir" => Some(Opcode::Eqir),
-            "eqri" => Some(Opcode::Eqri),
-            "eqrr" => Some(Opcode::Eqrr),
+// the program only halts when r0 is equal to the register compared by its only eqrr
+// instruction involving r0, returns the position of this instruction and the other register
+fn halting_comparison(program: &Program) -> Option<(usize, usize)> {
+    program
+        .instructions
+        .iter()
+        .enumerate()
+        .filter(|(_, inst)| inst.opcode == Opcode::Eqrr)
+        .find_map(|(ip, inst)| match inst.args {
+            [0, r, _] | [r, 0, _] if r != 0 => Some((ip, r as usize)),
             _ => None,
-        }
-    }
+        })
 }
 
-pub struct Inst {
-    opcode: Opcode,
-    args: [u32; 3],
+// runs the program once and yields every new value r0 is compared against, until a value repeats
+// the first value halts the program the fastest, the last one the slowest
+struct HaltingValues {
+    program: Compiled,
+    vm: Machine,
+    ip: Option<usize>,
+    check: Option<(usize, usize)>,
+    seen: HashSet<u64>,
 }
 
-pub struct Program {
-    ip_register: usize,
-    instructions: Vec<Inst>,
-}
-
-impl Program {
-    fn from_reader<R: io::Read>(r: R) -> Program {
-        let mut lines = BufReader::new(r).lines();
-
-        // parse first line
-        let ip_register = lines
-            .next()
-            .and_then(|s| s.ok())
-            .and_then(|s| s.chars().nth(4))
-            .and_then(|c| c.to_digit(10))
-            .unwrap_or(0) as usize;
-
-        // parse instructions
-        let inst_re = Regex::new(r"(\w+) (\d+) (\d+) (\d+)").unwrap();
-        let instructions = lines
-            .filter_map(|l| l.ok())
-            .filter_map(|s| {
-                inst_re.captures(s.as_str()).and_then(|c| {
-                    Some(Inst {
-                        opcode: Opcode::parse(c.get(1)?.as_str())?,
-                        args: [
-                            c.get(2)?.as_str().parse().ok()?,
-                            c.get(3)?.as_str().parse().ok()?,
-                            c.get(4)?.as_str().parse().ok()?,
-                        ],
-                    })
-                })
-            })
-            .collect();
-
-        Program {
-            ip_register,
-            instructions,
+impl HaltingValues {
+    fn new(program: &Program) -> Self {
+        let vm = Machine::new();
+        Self {
+            program: Compiled::new(program, vm.mode),
+            vm,
+            ip: Some(0),
+            check: halting_comparison(program),
+            seen: HashSet::new(),
         }
     }
+}
 
-    fn execute(&self, vm: &mut Machine) -> u64 {
-        self.execute_with_ip(vm, 0)
-    }
-
-    fn execute_with_ip(&self, vm: &mut Machine, ip: usize) -> u64 {
-        let mut n_exec = 0;
-        let mut ip = ip;
-
-        // fetch instruction
-        while let Some(inst) = self.instructions.get(ip as usize) {
-            // prepare ip register
-            vm.registers[self.ip_register] = ip as u64;
-
-            /*print!(
-                "ip={} {:?} {:?} {} {} {} ",
-                ip, vm.registers, inst.opcode, inst.args[0], inst.args[1], inst.args[2]
-            );*/
-
-            // exec
-            vm.exec(inst);
-
-            //println!("{:?}", vm.registers);
+impl Iterator for HaltingValues {
+    type Item = u64;
 
-            // restore ip
-            ip = vm.registers[self.ip_register] as usize;
+    fn next(&mut self) -> Option<u64> {
+        let (check_ip, reg) = self.check?;
 
-            // increment for next instruction
-            ip += 1;
-            n_exec += 1;
+        loop {
+            let ip = self.ip?;
+            let value = self.vm.registers[reg];
+            self.ip = self.program.step(&mut self.vm, ip).ok();
 
-            // security
-            if n_exec >= 12000 {
-                break;
+            if ip == check_ip {
+                if !self.seen.insert(value) {
+                    self.ip = None;
+                    return None;
+                }
+                return Some(value);
             }
-
-            //let _ = io::stdin().read(&mut [0u8]).unwrap();
         }
-
-        n_exec
-    }
-}
-
-struct Machine {
-    registers: [u64; 6],
-}
-
-impl Machine {
-    fn new() -> Self {
-        Self { registers: [0; 6] }
-    }
-
-    fn exec(&mut self, inst: &Inst) {
-        match inst.opcode {
-            Opcode::Addr => self.addr(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Addi => self.addi(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Mulr => self.mulr(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Muli => self.muli(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Banr => self.banr(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Bani => self.bani(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Borr => self.borr(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Bori => self.bori(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Setr => self.setr(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Seti => self.seti(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Gtir => self.gtir(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Gtri => self.gtri(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Gtrr => self.gtrr(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Eqir => self.eqir(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Eqri => self.eqri(inst.args[0], inst.args[1], inst.args[2]),
-            Opcode::Eqrr => self.eqrr(inst.args[0], inst.args[1], inst.args[2]),
-        }
-    }
-
-    fn addr(&mut self, r1: u32, r2: u32, r3: u32) {
-        let val = self.reg(r1) + self.reg(r2);
-        self.set_reg(r3, val);
-    }
-
-    fn addi(&mut self, r1: u32, v2: u32, r3: u32) {
-        let val = self.reg(r1) + v2 as u64;
-        self.set_reg(r3, val);
-    }
-
-    fn mulr(&mut self, r1: u32, r2: u32, r3: u32) {
-        let val = self.reg(r1) * self.reg(r2);
-        self.set_reg(r3, val);
-    }
-
-    fn muli(&mut self, r1: u32, v2: u32, r3: u32) {
-        let val = self.reg(r1) * v2 as u64;
-        self.set_reg(r3, val);
-    }
-
-    fn banr(&mut self, r1: u32, r2: u32, r3: u32) {
-        let val = self.reg(r1) & self.reg(r2);
-        self.set_reg(r3, val);
-    }
-
-    fn bani(&mut self, r1: u32, v2: u32, r3: u32) {
-        let val = self.reg(r1) & v2 as u64;
-        self.set_reg(r3, val);
-    }
-
-    fn borr(&mut self, r1: u32, r2: u32, r3: u32) {
-        let val = self.reg(r1) | self.reg(r2);
-        self.set_reg(r3, val);
-    }
-
-    fn bori(&mut self, r1: u32, v2: u32, r3: u32) {
-        let val = self.reg(r1) | v2 as u64;
-        self.set_reg(r3, val);
-    }
-
-    fn setr(&mut self, r1: u32, _: u32, r3: u32) {
-        let val = self.reg(r1);
-        self.set_reg(r3, val);
-    }
-
-    fn seti(&mut self, v1: u32, _: u32, r3: u32) {
-        self.set_reg(r3, v1 as u64);
-    }
-
-    fn gtir(&mut self, v1: u32, r2: u32, r3: u32) {
-        let val = if v1 as u64 > self.reg(r2) { 1 } else { 0 };
-        self.set_reg(r3, val);
-    }
-
-    fn gtri(&mut self, r1: u32, v2: u32, r3: u32) {
-        let val = if self.reg(r1) > v2 as u64 { 1 } else { 0 };
-        self.set_reg(r3, val);
-    }
-
-    fn gtrr(&mut self, r1: u32, r2: u32, r3: u32) {
-        let val = if self.reg(r1) > self.reg(r2) { 1 } else { 0 };
-        self.set_reg(r3, val);
-    }
-
-    fn eqir(&mut self, v1: u32, r2: u32, r3: u32) {
-        let val = if v1 as u64 == self.reg(r2) { 1 } else { 0 };
-        self.set_reg(r3, val);
-    }
-
-    fn eqri(&mut self, r1: u32, v2: u32, r3: u32) {
-        let val = if self.reg(r1) == v2 as u64 { 1 } else { 0 };
-        self.set_reg(r3, val);
-    }
-
-    fn eqrr(&mut self, r1: u32, r2: u32, r3: u32) {
-        let val = if self.reg(r1) == self.reg(r2) { 1 } else { 0 };
-        self.set_reg(r3, val);
-    }
-
-    fn reg(&self, n: u32) -> u64 {
-        self.registers[n as usize]
-    }
-
-    fn set_reg(&mut self, n: u32, val: u64) {
-        self.registers[n as usize] = val;
     }
 }
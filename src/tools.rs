@@ -0,0 +1,588 @@
+use crate::{
+    automaton::Automaton,
+    combat::{self, Event},
+    elfcode::{
+        assemble, decode, encode, optimise, parse_mapping, Budget, Compiled, Debugger, Execution,
+        Machine, Mode, Opcode, Outcome, Profile, Program,
+    },
+    fuzz,
+    grid::Grid,
+    point::Point,
+    pots::Garden,
+    route::{parse_route, Facility},
+    search::{astar, bfs, dijkstra},
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io,
+    time::{Duration, Instant},
+};
+
+pub type ToolResult = Result<(), Box<dyn Error>>;
+
+// runs the tool with the given name, returns None if no such tool exists
+pub fn exec_tool(name: &str, args: &[String]) -> Option<ToolResult> {
+    let result = match name {
+        "elfcode-debug" => elfcode_debug(args),
+        "elfcode-profile" => elfcode_profile(args),
+        "elfcode-bench" => elfcode_bench(args),
+        "elfcode-asm" => elfcode_asm(args),
+        "elfcode-decode" => elfcode_decode(args),
+        "elfcode-opt" => elfcode_opt(args),
+        "elfcode-fuzz" => elfcode_fuzz(args),
+        "grid-path" => grid_path(args),
+        "life" => life(args),
+        "plants" => plants(args),
+        "rooms-map" => rooms_map(args),
+        "rooms-regex" => rooms_regex(args),
+        "rooms-far" => rooms_far(args),
+        "combat-log" => combat_log(args),
+        "combat-replay" => combat_replay(args),
+        "combat-render" => combat_render(args),
+        "combat-golden" => combat_golden(args),
+        "combat-flawless" => combat_flawless(args),
+        "combat-whatif" => combat_whatif(args),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn load_program(path: Option<&String>) -> Result<Program, Box<dyn Error>> {
+    let path = path.ok_or("missing program file")?;
+    Ok(Program::from_reader(File::open(path)?))
+}
+
+fn load_mapping(path: Option<&String>) -> Result<Vec<Opcode>, Box<dyn Error>> {
+    let path = path.ok_or("missing opcode mapping file")?;
+    Ok(parse_mapping(&fs::read_to_string(path)?)?)
+}
+
+// a machine with r0 and the arithmetic mode given on the command line, if any
+fn load_machine(r0: Option<&String>, mode: Option<&String>) -> Result<Machine, Box<dyn Error>> {
+    let mode = match mode {
+        Some(m) => Mode::parse(m).ok_or_else(|| format!("invalid mode: {}", m))?,
+        None => Mode::default(),
+    };
+
+    let mut vm = Machine::with_mode(mode);
+    if let Some(r0) = r0 {
+        vm.registers[0] = r0.parse()?;
+    }
+
+    Ok(vm)
+}
+
+// a budget is either a number of instructions, or a number of seconds such as "10s"
+fn parse_budget(s: &str) -> Result<Budget, Box<dyn Error>> {
+    Ok(if let Some(secs) = s.strip_suffix('s') {
+        Budget::Time(Duration::from_secs_f64(secs.parse()?))
+    } else {
+        Budget::Instructions(s.parse()?)
+    })
+}
+
+// usage: elfcode-debug <program> [r0] [mode]
+fn elfcode_debug(args: &[String]) -> ToolResult {
+    let program = load_program(args.first())?;
+    let vm = load_machine(args.get(1), args.get(2))?;
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    Debugger::new(&program, vm).run(stdin.lock(), stdout.lock())?;
+
+    Ok(())
+}
+
+// usage: elfcode-profile <program> [r0] [budget] [mode]
+fn elfcode_profile(args: &[String]) -> ToolResult {
+    let program = load_program(args.first())?;
+
+    let mut vm = load_machine(args.get(1), args.get(3))?;
+
+    let budget = match args.get(2) {
+        Some(b) => parse_budget(b)?,
+        None => Budget::Instructions(100_000_000),
+    };
+
+    let profile = Profile::run(&program, &mut vm, budget);
+
+    let stdout = io::stdout();
+    profile.report(&mut stdout.lock())?;
+
+    Ok(())
+}
+
+// usage: elfcode-bench <program> [r0] [budget] [mode]
+// runs the program with both the interpreter and the compiler, and checks they agree
+fn elfcode_bench(args: &[String]) -> ToolResult {
+    let program = load_program(args.first())?;
+
+    let vm = load_machine(args.get(1), args.get(3))?;
+
+    let budget = match args.get(2) {
+        Some(b) => parse_budget(b)?,
+        None => Budget::Instructions(100_000_000),
+    };
+
+    let timed = |name: &str, exec: &dyn Fn(&mut Machine) -> Execution| {
+        let mut vm = vm.clone();
+        let start = Instant::now();
+        let execution = exec(&mut vm);
+        let elapsed = start.elapsed();
+
+        println!(
+            "{:<12} {:>12} instructions in {:>8.3}s ({:.1} M/s), {}",
+            name,
+            execution.n_exec,
+            elapsed.as_secs_f64(),
+            execution.n_exec as f64 / elapsed.as_secs_f64() / 1e6,
+            execution.outcome
+        );
+
+        execution
+    };
+
+    println!("mode: {}", vm.mode);
+    let interpreted = timed("interpreter", &|vm| program.execute(vm, budget));
+
+    let compiled = Compiled::new(&program, vm.mode);
+    let compiled = timed("compiled", &|vm| compiled.execute(vm, budget));
+
+    if interpreted != compiled {
+        return Err(format!("results differ: {:?} != {:?}", interpreted, compiled).into());
+    }
+
+    println!("identical results: {:?}", compiled.registers);
+    Ok(())
+}
+
+// usage: elfcode-asm <source> [mapping]
+// without a mapping, prints the program in the day 19 format, otherwise in the day 16 format
+fn elfcode_asm(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing source file")?;
+    let program = assemble(&fs::read_to_string(path)?)?;
+
+    if args.get(1).is_none() {
+        print!("{}", program);
+        return Ok(());
+    }
+
+    let mapping = load_mapping(args.get(1))?;
+    let encoded = encode(&program, &mapping)?;
+
+    // make sure nothing got lost on the way
+    if decode(&encoded, &mapping)? != program {
+        return Err("encoded program does not decode to the original one".into());
+    }
+
+    print!("{}", encoded);
+    Ok(())
+}
+
+// usage: elfcode-decode <numeric program> <mapping>
+fn elfcode_decode(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing program file")?;
+    let mapping = load_mapping(args.get(1))?;
+    let program = decode(&fs::read_to_string(path)?, &mapping)?;
+
+    print!("{}", program);
+    Ok(())
+}
+
+// usage: elfcode-opt <program> [r0] [budget] [mode]
+// prints the optimised program, then runs both versions and checks they end the same way,
+// the report goes to stderr so that the output can be used as a program
+fn elfcode_opt(args: &[String]) -> ToolResult {
+    let program = load_program(args.first())?;
+    let vm = load_machine(args.get(1), args.get(3))?;
+
+    let optimised = optimise(&program, vm.mode);
+    print!("{}", optimised);
+
+    let budget = match args.get(2) {
+        Some(b) => parse_budget(b)?,
+        None => Budget::Instructions(100_000_000),
+    };
+
+    let original = program.execute(&mut vm.clone(), budget);
+    let result = optimised.execute(&mut vm.clone(), budget);
+
+    eprintln!(
+        "{} -> {} instructions, {} -> {} executed",
+        program.instructions.len(),
+        optimised.instructions.len(),
+        original.n_exec,
+        result.n_exec
+    );
+
+    // faults happen at a different ip, and the ip register itself is expected to differ
+    if original.outcome == Outcome::BudgetExhausted || result.outcome == Outcome::BudgetExhausted {
+        return Err("budget exhausted, results not compared".into());
+    }
+    let ends = |e: &Execution| {
+        let mut registers = e.registers;
+        registers[program.ip_register] = 0;
+        (e.outcome == Outcome::Halted, registers)
+    };
+    if ends(&original) != ends(&result) {
+        return Err(format!("results differ: {:?} != {:?}", original, result).into());
+    }
+
+    eprintln!("identical results: {:?}", result.registers);
+    Ok(())
+}
+
+// usage: elfcode-fuzz [cases] [seed]
+// runs random programs on every elfcode implementation, the default seed gives the same cases
+// on every run
+fn elfcode_fuzz(args: &[String]) -> ToolResult {
+    let n_cases = match args.first() {
+        Some(n) => n.parse()?,
+        None => 100_000,
+    };
+    let seed = match args.get(1) {
+        Some(s) => s.parse()?,
+        None => 2018,
+    };
+
+    if let Some(divergence) = fuzz::run(seed, n_cases) {
+        print!("{}", divergence);
+        return Err("implementations differ".into());
+    }
+
+    println!("{} cases with seed {}, no divergence", n_cases, seed);
+    Ok(())
+}
+
+// usage: grid-path <map> [4|8]
+// finds the cheapest path from S to E with every search, moving to the 4 or 8 neighbours,
+// entering a cell with a digit costs that digit, any other cell but a wall costs 1
+fn grid_path(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing map file")?;
+    let mut map = Grid::from_reader(File::open(path)?, Some)?;
+    let diagonals = match args.get(1).map(|s| s.as_str()) {
+        None | Some("4") => false,
+        Some("8") => true,
+        Some(n) => return Err(format!("invalid number of neighbours: {}", n).into()),
+    };
+
+    let find = |c: char| {
+        map.points()
+            .find(|&p| map.get(p) == Some(&c))
+            .ok_or_else(|| format!("no {} on the map", c))
+    };
+    let start = find('S')?;
+    let end = find('E')?;
+
+    let cost = |p: Point| match map.get(p) {
+        Some('#') | None => None,
+        Some(c) => Some(c.to_digit(10).filter(|&d| d > 0).unwrap_or(1) as u64),
+    };
+    let neighbours = |p: Point| {
+        let neighbours: Vec<_> = if diagonals {
+            map.neighbours8(p).collect()
+        } else {
+            map.neighbours4(p).collect()
+        };
+        neighbours
+            .into_iter()
+            .filter_map(move |n| Some((n, cost(n)?)))
+    };
+    // every step costs at least 1
+    let heuristic = |p: &Point| {
+        if diagonals {
+            p.chebyshev(&end) as u64
+        } else {
+            p.manhattan(&end) as u64
+        }
+    };
+
+    let steps = bfs(start, |p| neighbours(p).map(|(n, _)| n), |&p| p);
+    let costs = dijkstra(start, neighbours, |&p| p);
+    let (best_cost, best_path) =
+        astar(start, |&p| p == end, neighbours, heuristic, |&p| p).ok_or("no path from S to E")?;
+
+    println!(
+        "{}x{} map, {} cells reachable",
+        map.width(),
+        map.height(),
+        steps.costs().count()
+    );
+    println!("bfs: {} steps", steps.cost(end).unwrap_or_default());
+    println!("dijkstra: cost {}", costs.cost(end).unwrap_or_default());
+    println!("a*: cost {}, {} steps", best_cost, best_path.len() - 1);
+
+    if costs.cost(end) != Some(best_cost) {
+        return Err("dijkstra and a* disagree".into());
+    }
+    let path_cost: u64 = best_path.iter().skip(1).filter_map(|&p| cost(p)).sum();
+    if path_cost != best_cost {
+        return Err(format!("a* path costs {}, not {}", path_cost, best_cost).into());
+    }
+
+    for &p in &best_path[1..best_path.len() - 1] {
+        map.set(p, '*');
+    }
+    print!("{}", map);
+
+    Ok(())
+}
+
+// usage: life <map> [rule] [generations]
+// runs a life-like automaton on a map of # and . cells, the rule is written like B3/S23,
+// everything outside the map stays dead
+fn life(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing map file")?;
+    let cells = Grid::from_reader(File::open(path)?, |c| match c {
+        '#' => Some(true),
+        '.' => Some(false),
+        _ => None,
+    })?;
+    let (born, survive) = parse_life_rule(args.get(1).map_or("B3/S23", |s| s.as_str()))?;
+    let generations = match args.get(2) {
+        Some(n) => n.parse()?,
+        None => 1,
+    };
+
+    let mut life = Automaton::new(cells);
+    for _ in 0..generations {
+        life.step(|alive, counts| {
+            if alive {
+                survive[counts[1]]
+            } else {
+                born[counts[1]]
+            }
+        });
+    }
+
+    for row in life.cells().rows() {
+        let row: String = row.iter().map(|&a| if a { '#' } else { '.' }).collect();
+        println!("{}", row);
+    }
+    let alive = life.cells().iter().filter(|&&a| a).count();
+    println!("{} alive after {} generations", alive, generations);
+
+    Ok(())
+}
+
+// the numbers of live neighbours for which a dead cell is born, and a live one survives
+fn parse_life_rule(s: &str) -> Result<([bool; 9], [bool; 9]), Box<dyn Error>> {
+    let parse_counts = |part: Option<&str>, prefix: char| {
+        let digits = part
+            .and_then(|p| p.strip_prefix(prefix))
+            .ok_or_else(|| format!("invalid rule: {}", s))?;
+
+        let mut counts = [false; 9];
+        for d in digits.chars() {
+            let n = d
+                .to_digit(10)
+                .filter(|&n| n <= 8)
+                .ok_or_else(|| format!("invalid neighbour count: {}", d))?;
+            counts[n as usize] = true;
+        }
+        Ok::<_, String>(counts)
+    };
+
+    let mut parts = s.split('/');
+    let born = parse_counts(parts.next(), 'B')?;
+    let survive = parse_counts(parts.next(), 'S')?;
+    if parts.next().is_some() {
+        return Err(format!("invalid rule: {}", s).into());
+    }
+
+    Ok((born, survive))
+}
+
+// usage: plants <input> <generations>
+// the sum of the pot numbers with a plant after any number of generations of day 12
+fn plants(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing input file")?;
+    let generations = args.get(1).ok_or("missing generation count")?.parse()?;
+
+    let garden = Garden::from_reader(File::open(path)?);
+    println!("{}", garden.plants_sum_after(generations));
+
+    Ok(())
+}
+
+// usage: rooms-map <route regex>
+// draws the rooms and doors of day 20 described by the regex
+fn rooms_map(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing regex file")?;
+    let route = parse_route(&fs::read_to_string(path)?)?;
+    print!("{}", Facility::from_route(&route));
+
+    Ok(())
+}
+
+// usage: rooms-regex <route regex>
+// checks the syntax of a day 20 regex, and prints it back from the parsed route
+fn rooms_regex(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing regex file")?;
+    let route = parse_route(&fs::read_to_string(path)?)?;
+
+    let regex = route.to_regex();
+    if parse_route(&regex)? != route {
+        return Err("the printed regex doesn't parse back to the same route".into());
+    }
+    println!("{}", regex);
+
+    Ok(())
+}
+
+// usage: rooms-far <route regex> <doors>
+// counts the day 20 rooms that need at least this many doors to be reached
+fn rooms_far(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing regex file")?;
+    let doors = args.get(1).ok_or("missing door count")?.parse()?;
+    let route = parse_route(&fs::read_to_string(path)?)?;
+
+    println!("{}", Facility::from_route(&route).rooms_at_least(doors));
+
+    Ok(())
+}
+
+// usage: combat-log <map> [elf attack]
+// fights a day 15 battle and writes what happens as json, one event per line
+fn combat_log(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing map file")?;
+    let mut map = combat::Map::from_reader(File::open(path)?);
+    if let Some(atk) = args.get(1) {
+        map.set_elf_atk(atk.parse()?);
+    }
+
+    map.record_events();
+    map.battle();
+
+    for e in map.take_events() {
+        println!("{}", serde_json::to_string(&e)?);
+    }
+
+    Ok(())
+}
+
+// usage: combat-replay <map> <log> [round]
+// rebuilds the board of a logged battle as it was at the end of a round, the last one by default
+fn combat_replay(args: &[String]) -> ToolResult {
+    let map_path = args.first().ok_or("missing map file")?;
+    let log_path = args.get(1).ok_or("missing log file")?;
+    let round = match args.get(2) {
+        Some(r) => Some(r.parse::<usize>()?),
+        None => None,
+    };
+
+    let mut map = combat::Map::from_reader(File::open(map_path)?);
+    if round != Some(0) {
+        for (i, l) in fs::read_to_string(log_path)?.lines().enumerate() {
+            let event =
+                serde_json::from_str::<Event>(l).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            map.apply(&event)
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+
+            if let Event::RoundCompleted { round: r } = event {
+                if Some(r) == round {
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Some(r) = round {
+        if map.rounds() < r {
+            return Err(format!("the battle ended after {} rounds", map.rounds()).into());
+        }
+    }
+
+    println!("After {} rounds:", map.rounds());
+    print!("{}", map);
+
+    Ok(())
+}
+
+// usage: combat-render <map> [elf attack]
+// prints a day 15 battle round by round, the way the puzzle does
+fn combat_render(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing map file")?;
+    let mut map = combat::Map::from_reader(File::open(path)?);
+    if let Some(atk) = args.get(1) {
+        map.set_elf_atk(atk.parse()?);
+    }
+
+    print!("{}", map.render_battle());
+
+    Ok(())
+}
+
+// usage: combat-golden <dir>
+// renders the battle of every .map file in the directory, and compares it with the rounds
+// and outcome found in the .txt file of the same name
+fn combat_golden(args: &[String]) -> ToolResult {
+    let dir = args.first().ok_or("missing directory")?;
+
+    let mut maps = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    maps.retain(|p| p.extension().is_some_and(|e| e == "map"));
+    maps.sort();
+
+    let mut failed = 0;
+    for path in maps {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let expected = fs::read_to_string(path.with_extension("txt"))?;
+        let actual = combat::Map::from_reader(File::open(&path)?).render_battle();
+
+        let mismatch = combat::golden_mismatch(&expected, &actual);
+
+        match mismatch {
+            None => println!("{}: ok", name),
+            Some((expected, actual)) => {
+                failed += 1;
+                let header = expected.lines().next().unwrap_or_default();
+                println!("{}: differs at {}", name, header);
+                println!("expected:\n{}", expected);
+                println!("actual:\n{}", actual);
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} battles differ", failed).into());
+    }
+
+    Ok(())
+}
+
+// usage: combat-flawless <map>
+// finds the lowest elf attack power that wins a day 15 battle without losing any elf
+fn combat_flawless(args: &[String]) -> ToolResult {
+    let path = args.first().ok_or("missing map file")?;
+    let map = combat::Map::from_reader(File::open(path)?);
+
+    let v = combat::flawless_victory(&map).ok_or("the elves always lose someone")?;
+    println!(
+        "attack power {}: {} rounds, {} hit points left, outcome {}",
+        v.elf_atk,
+        v.rounds,
+        v.hp,
+        v.outcome()
+    );
+
+    Ok(())
+}
+
+// usage: combat-whatif <map> <config>
+// prints a day 15 battle round by round with other unit stats, or more factions,
+// the config is json, eg. {"factions": {"E": {"hp": 300}, "O": {"atk": 5}}, "free_for_all": true}
+fn combat_whatif(args: &[String]) -> ToolResult {
+    let map_path = args.first().ok_or("missing map file")?;
+    let config_path = args.get(1).ok_or("missing config file")?;
+
+    let mut map = combat::Map::from_reader(File::open(map_path)?);
+    map.set_config(serde_json::from_str(&fs::read_to_string(config_path)?)?);
+
+    print!("{}", map.render_battle());
+
+    Ok(())
+}